@@ -1,5 +1,6 @@
 use crate::big_vec2::BigVec2;
 use dashu::float::FBig;
+use shared::formula::Quadratic;
 use std::ops::*;
 
 #[derive(Clone, Debug)]
@@ -12,11 +13,6 @@ impl Complex {
         Self(BigVec2::new(x, y))
     }
 
-    pub fn square(self) -> Self {
-        const TWO: FBig = dashu::fbig!(10);
-        Self::new(self.x.sqr() - self.y.sqr(), self.0.x * self.0.y * TWO)
-    }
-
     pub fn norm_squared(&self) -> FBig {
         self.0.length_squared()
     }
@@ -26,6 +22,24 @@ impl Complex {
     }
 }
 
+impl Quadratic for Complex {
+    fn square(self) -> Self {
+        const TWO: FBig = dashu::fbig!(10);
+        Self::new(self.x.sqr() - self.y.sqr(), self.0.x * self.0.y * TWO)
+    }
+
+    fn fold(self, fold: impl Fn(glam::Vec2) -> glam::Vec2) -> Self {
+        let sign = |x: &FBig| if *x < FBig::ZERO { -1.0 } else { 1.0 };
+        let signs = glam::vec2(sign(&self.x), sign(&self.y));
+        let flips = fold(signs) * signs;
+        let BigVec2 { x, y } = self.0;
+        Self::new(
+            if flips.x < 0.0 { -x } else { x },
+            if flips.y < 0.0 { -y } else { y },
+        )
+    }
+}
+
 impl Deref for Complex {
     type Target = BigVec2;
 
@@ -0,0 +1,74 @@
+use super::Controller;
+use super::gradient::{srgb_edit_button, to_color32};
+use easy_shader_runner::egui;
+use shared::push_constants::shader::*;
+
+/// The built-in palettes that can be cloned into the editor
+const BUILT_IN: [Palette; 10] = [
+    Palette::Pastel,
+    Palette::Zebra,
+    Palette::Copper,
+    Palette::SolarizedDark,
+    Palette::Highlighter,
+    Palette::RedAndBlack,
+    Palette::RGB,
+    Palette::NeonA,
+    Palette::NeonB,
+    Palette::NeonC,
+];
+
+/// A colour picker for the offset and amplitude, the only vectors that are colours. The
+/// frequency and phase are edited with `slider_row`
+fn colour_row(ui: &mut egui::Ui, label: &str, v: &mut [f32; 3]) {
+    ui.horizontal(|ui| {
+        srgb_edit_button(ui, v);
+        for x in v.iter_mut() {
+            ui.add(egui::DragValue::new(x).range(0.0..=1.0).speed(0.005));
+        }
+        ui.label(label);
+    });
+}
+
+fn slider_row(ui: &mut egui::Ui, label: &str, v: &mut [f32; 3], max: f32) {
+    ui.horizontal(|ui| {
+        for x in v.iter_mut() {
+            ui.add(egui::DragValue::new(x).range(0.0..=max).speed(0.005));
+        }
+        ui.label(label);
+    });
+}
+
+impl Controller {
+    pub fn cosine_palette_ui(&mut self, ui: &mut egui::Ui) {
+        let cosine = &mut self.cosine_palette;
+        let width = ui.available_width();
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(width, 20.0), egui::Sense::hover());
+        let segments = 64;
+        for k in 0..segments {
+            let t0 = k as f32 / segments as f32;
+            let t1 = (k + 1) as f32 / segments as f32;
+            ui.painter().rect_filled(
+                egui::Rect::from_x_y_ranges(
+                    rect.left() + t0 * width..=rect.left() + t1 * width,
+                    rect.y_range(),
+                ),
+                0.0,
+                to_color32(cosine.col(0.5 * (t0 + t1))),
+            );
+        }
+
+        colour_row(ui, "Offset", &mut cosine.a);
+        colour_row(ui, "Amplitude", &mut cosine.b);
+        slider_row(ui, "Frequency", &mut cosine.c, 4.0);
+        slider_row(ui, "Phase", &mut cosine.d, 1.0);
+
+        ui.label("Clone");
+        ui.horizontal_wrapped(|ui| {
+            for palette in BUILT_IN {
+                if ui.small_button(format!("{palette:?}")).clicked() {
+                    *cosine = palette.cosine(*cosine);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,272 @@
+use super::Controller;
+use easy_shader_runner::{GraphicsContext, egui, wgpu};
+use glam::*;
+use shared::colour::col_from_orbit;
+use shared::colour_space::encode_srgb;
+use shared::grid::Grid;
+use shared::orbit::Pixel;
+use shared::push_constants::shader::*;
+use shared::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Gif,
+    Apng,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "gif",
+            ExportFormat::Apng => "png",
+        }
+    }
+}
+
+pub struct PaletteCycleExport {
+    format: ExportFormat,
+    num_frames: u32,
+    scale: f32,
+    pub requested: bool,
+    status: Option<String>,
+}
+
+impl Default for PaletteCycleExport {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Gif,
+            num_frames: 60,
+            scale: 0.5,
+            requested: false,
+            status: None,
+        }
+    }
+}
+
+impl Controller {
+    pub fn export_ui(&mut self, ui: &mut egui::Ui) {
+        let loops = loop_period(self.shown_colourings()).is_some();
+        ui.collapsing(egui::RichText::new("Export").size(15.0), |ui| {
+            let export = &mut self.palette_cycle_export;
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut export.format, ExportFormat::Gif, "GIF");
+                ui.radio_value(&mut export.format, ExportFormat::Apng, "APNG");
+            });
+            ui.label("Frames");
+            ui.add(egui::Slider::new(&mut export.num_frames, 2..=240));
+            ui.label("Scale");
+            ui.add(egui::Slider::new(&mut export.scale, 0.1..=1.0));
+            let size = (self.size.as_vec2() * export.scale)
+                .max(Vec2::ONE)
+                .as_uvec2();
+            ui.label(format!("{} x {}", size.x, size.y));
+            if ui
+                .add_enabled(loops, egui::Button::new("Export Palette Cycle"))
+                .clicked()
+            {
+                export.requested = true;
+            }
+            if !loops {
+                ui.label("A clamped palette doesn't cycle, set its wrap to repeat or mirror");
+            }
+            if let Some(status) = &export.status {
+                ui.label(status);
+            }
+        });
+    }
+
+    pub fn export_palette_cycle(&mut self, graphics_context: &GraphicsContext) {
+        self.palette_cycle_export.requested = false;
+        let result = self.write_palette_cycle(graphics_context);
+        self.palette_cycle_export.status = Some(match result {
+            Ok(path) => format!("Saved {}", path.display()),
+            Err(err) => format!("Export failed: {err}"),
+        });
+    }
+
+    fn write_palette_cycle(
+        &self,
+        graphics_context: &GraphicsContext,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let PaletteCycleExport {
+            format,
+            num_frames,
+            scale,
+            ..
+        } = self.palette_cycle_export;
+        if self.size.x * self.size.y > self.grid_cells {
+            return Err("the window has grown past the cached grids".into());
+        }
+        let grid = self.read_grid(graphics_context, self.grid_buffer.as_ref().unwrap());
+        let feature_grid =
+            self.read_grid(graphics_context, self.feature_grid_buffer.as_ref().unwrap());
+        let sum_grid = self.read_grid(graphics_context, self.sum_grid_buffer.as_ref().unwrap());
+        let out_size = (self.size.as_vec2() * scale).max(Vec2::ONE).as_uvec2();
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let path = PathBuf::from(format!("palette_cycle_{timestamp}.{}", format.extension()));
+
+        // Shifting `animate_time` by a whole period of every palette gives back the first frame,
+        // so the last frame flows seamlessly into the first
+        let period =
+            loop_period(self.shown_colourings()).ok_or("a clamped palette doesn't cycle")?;
+        let sgn = if self.animate.reverse { -1.0 } else { 1.0 };
+        let loop_seconds = period / self.animate.speed.max(1e-3);
+        let frame_delay_ms = (1000.0 * loop_seconds / num_frames as f32).clamp(10.0, 65535.0);
+        let mut constants = self.fragment_constants();
+        let start_time = self.animate.value;
+        let colourings = self.colourings();
+        let frames = (0..num_frames).map(|frame| {
+            constants.animate_time = start_time + sgn * period * frame as f32 / num_frames as f32;
+            rgb_frame(
+                &constants,
+                &colourings,
+                &grid,
+                &feature_grid,
+                &sum_grid,
+                self.size,
+                out_size,
+            )
+        });
+
+        let writer = BufWriter::new(File::create(&path)?);
+        match format {
+            ExportFormat::Gif => {
+                let mut encoder =
+                    gif::Encoder::new(writer, out_size.x as u16, out_size.y as u16, &[])?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                for rgb in frames {
+                    let mut frame =
+                        gif::Frame::from_rgb_speed(out_size.x as u16, out_size.y as u16, &rgb, 10);
+                    frame.delay = (frame_delay_ms / 10.0).round() as u16;
+                    encoder.write_frame(&frame)?;
+                }
+            }
+            ExportFormat::Apng => {
+                let mut encoder = png::Encoder::new(writer, out_size.x, out_size.y);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(num_frames, 0)?;
+                encoder.set_frame_delay(frame_delay_ms.round() as u16, 1000)?;
+                let mut writer = encoder.write_header()?;
+                for rgb in frames {
+                    writer.write_image_data(&rgb)?;
+                }
+                writer.finish()?;
+            }
+        }
+        Ok(path)
+    }
+
+    /// Copies the visible rows of a cached grid, like the render parameters, back from the gpu
+    fn read_grid<T: bytemuck::AnyBitPattern>(
+        &self,
+        graphics_context: &GraphicsContext,
+        grid_buffer: &wgpu::Buffer,
+    ) -> Grid<T> {
+        let device = &graphics_context.device;
+        let size =
+            (std::mem::size_of::<T>() as u32 * self.size.x * self.size.y) as wgpu::BufferAddress;
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("grid_staging_buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("grid_readback_encoder"),
+        });
+        encoder.copy_buffer_to_buffer(grid_buffer, 0, &staging_buffer, 0, size);
+        graphics_context.queue.submit(Some(encoder.finish()));
+
+        let slice = staging_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::PollType::Wait).unwrap();
+        let buffer = bytemuck::pod_collect_to_vec(&slice.get_mapped_range()[..]);
+        staging_buffer.unmap();
+        Grid {
+            size: self.size,
+            buffer,
+        }
+    }
+}
+
+/// How far `animate_time` has to move for every colouring to come back to where it started,
+/// or `None` when one of them is clamped, and never comes back
+fn loop_period<'a>(colourings: impl IntoIterator<Item = &'a Colouring>) -> Option<f32> {
+    colourings
+        .into_iter()
+        .try_fold(1.0, |period: f32, colouring| {
+            let palette_period = colouring.palette.period();
+            let colouring_period = match colouring.wrap {
+                Wrap::Repeat => palette_period,
+                // Runs along the palette and back again
+                Wrap::Mirror => 2.0 * palette_period,
+                Wrap::Clamp => return None,
+            };
+            Some(period.max(colouring_period))
+        })
+}
+
+fn rgb_frame(
+    constants: &FragmentConstants,
+    colourings: &Colourings,
+    grid: &Grid<OrbitEnd>,
+    feature_grid: &Grid<OrbitFeatures>,
+    sum_grid: &Grid<OrbitSums>,
+    size: UVec2,
+    out_size: UVec2,
+) -> Vec<u8> {
+    let scale = size.as_vec2() / out_size.as_vec2();
+    let mut rgb = Vec::with_capacity((out_size.x * out_size.y * 3) as usize);
+    for y in 0..out_size.y {
+        for x in 0..out_size.x {
+            let p = ((uvec2(x, y).as_vec2() + 0.5) * scale)
+                .as_uvec2()
+                .min(size - 1);
+            let pixel = Pixel::new(constants, p.as_vec2() + 0.5);
+            let col = col_from_orbit(
+                constants,
+                colourings,
+                pixel,
+                grid.get(p),
+                feature_grid.get(p),
+                sum_grid.get(p),
+            );
+            let col = (encode_srgb(col, uvec2(x, y), constants.dither) * 255.0).round();
+            rgb.extend([col.x as u8, col.y as u8, col.z as u8]);
+        }
+    }
+    rgb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colouring(palette: Palette, wrap: Wrap) -> Colouring {
+        Colouring {
+            palette,
+            wrap,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn loop_period_covers_every_palette() {
+        let repeat = colouring(Palette::Pastel, Wrap::Repeat);
+        let neon = colouring(Palette::NeonA, Wrap::Repeat);
+        let mirror = colouring(Palette::Pastel, Wrap::Mirror);
+        let neon_mirror = colouring(Palette::NeonA, Wrap::Mirror);
+        let clamp = colouring(Palette::Pastel, Wrap::Clamp);
+        assert_eq!(loop_period([&repeat]), Some(1.0));
+        assert_eq!(loop_period([&repeat, &neon]), Some(2.0));
+        assert_eq!(loop_period([&mirror]), Some(2.0));
+        assert_eq!(loop_period([&neon, &neon_mirror]), Some(4.0));
+        assert_eq!(loop_period([&repeat, &clamp]), None);
+    }
+}
@@ -0,0 +1,186 @@
+use super::Controller;
+use easy_shader_runner::egui;
+use glam::*;
+use shared::palette;
+use shared::push_constants::shader::*;
+use shared::*;
+
+pub struct Gradient {
+    /// Kept in the order they were added, so that the editor can refer to a stop by its index
+    /// while it is dragged past others
+    pub stops: Vec<GradientStop>,
+    /// `stops` sorted by position, as uploaded to the gpu
+    pub sorted_stops: Vec<GradientStop>,
+    pub interpolation: Interpolation,
+    selected: usize,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        let stop = |position, r, g, b| GradientStop {
+            colour: [r, g, b],
+            position,
+        };
+        let mut gradient = Self {
+            stops: vec![
+                stop(0.0, 0.0, 0.03, 0.39),
+                stop(0.16, 0.13, 0.42, 0.8),
+                stop(0.42, 0.93, 1.0, 1.0),
+                stop(0.64, 1.0, 0.67, 0.0),
+                stop(0.86, 0.0, 0.01, 0.0),
+            ],
+            sorted_stops: vec![],
+            interpolation: Interpolation::default(),
+            selected: 0,
+        };
+        gradient.sort();
+        gradient
+    }
+}
+
+impl Gradient {
+    pub fn set_stops(&mut self, mut stops: Vec<GradientStop>) {
+        if stops.is_empty() {
+            return;
+        }
+        stops.truncate(MAX_GRADIENT_STOPS as usize);
+        self.stops = stops;
+        self.selected = 0;
+        self.sort();
+    }
+
+    pub fn sample(&self, t: f32) -> Vec3 {
+        palette::gradient(
+            |k| self.sorted_stops[k],
+            self.sorted_stops.len() as u32,
+            self.interpolation,
+            t,
+        )
+    }
+
+    fn sort(&mut self) {
+        self.sorted_stops = self.stops.clone();
+        self.sorted_stops
+            .sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+}
+
+pub fn to_color32(col: Vec3) -> egui::Color32 {
+    let col = (col.clamp(Vec3::ZERO, Vec3::ONE) * 255.0).round();
+    egui::Color32::from_rgb(col.x as u8, col.y as u8, col.z as u8)
+}
+
+/// A colour picker for an sRGB colour. egui takes float colours to be linear, so the picker
+/// works on bytes instead, and the colour is only written back when it was edited
+pub fn srgb_edit_button(ui: &mut egui::Ui, colour: &mut [f32; 3]) -> bool {
+    let col = to_color32(Vec3::from_array(*colour));
+    let mut rgb = [col.r(), col.g(), col.b()];
+    let changed = ui.color_edit_button_srgb(&mut rgb).changed();
+    if changed {
+        *colour = rgb.map(|c| c as f32 / 255.0);
+    }
+    changed
+}
+
+impl Controller {
+    pub fn gradient_ui(&mut self, ui: &mut egui::Ui) {
+        let gradient = &mut self.gradient;
+        let width = ui.available_width();
+        let (bar_rect, bar_response) =
+            ui.allocate_exact_size(egui::vec2(width, 20.0), egui::Sense::click());
+        let (marker_rect, _) =
+            ui.allocate_exact_size(egui::vec2(width, 12.0), egui::Sense::hover());
+        let painter = ui.painter();
+        let mut changed = false;
+
+        let segments = 64;
+        for k in 0..segments {
+            let t0 = k as f32 / segments as f32;
+            let t1 = (k + 1) as f32 / segments as f32;
+            painter.rect_filled(
+                egui::Rect::from_x_y_ranges(
+                    bar_rect.left() + t0 * width..=bar_rect.left() + t1 * width,
+                    bar_rect.y_range(),
+                ),
+                0.0,
+                to_color32(gradient.sample(0.5 * (t0 + t1))),
+            );
+        }
+        // Clicking the bar adds a stop with the colour already there
+        if bar_response.clicked()
+            && gradient.stops.len() < MAX_GRADIENT_STOPS as usize
+            && let Some(pos) = bar_response.interact_pointer_pos()
+        {
+            let position = ((pos.x - bar_rect.left()) / width).clamp(0.0, 1.0);
+            let colour = gradient.sample(position);
+            gradient.stops.push(GradientStop {
+                colour: colour.to_array(),
+                position,
+            });
+            gradient.selected = gradient.stops.len() - 1;
+            changed = true;
+        }
+
+        for (i, stop) in gradient.stops.iter_mut().enumerate() {
+            let x = marker_rect.left() + stop.position * width;
+            let rect = egui::Rect::from_center_size(
+                egui::pos2(x, marker_rect.center().y),
+                egui::vec2(10.0, marker_rect.height()),
+            );
+            let response =
+                ui.interact(rect, bar_response.id.with(i), egui::Sense::click_and_drag());
+            if response.clicked() || response.drag_started() {
+                gradient.selected = i;
+            }
+            if response.dragged() {
+                stop.position = (stop.position + response.drag_delta().x / width).clamp(0.0, 1.0);
+                changed = true;
+            }
+            let stroke = if i == gradient.selected {
+                egui::Stroke::new(2.0, egui::Color32::WHITE)
+            } else {
+                egui::Stroke::new(1.0, egui::Color32::GRAY)
+            };
+            painter.add(egui::Shape::convex_polygon(
+                vec![
+                    egui::pos2(x, rect.top()),
+                    egui::pos2(rect.right(), rect.bottom()),
+                    egui::pos2(rect.left(), rect.bottom()),
+                ],
+                to_color32(Vec3::from_array(stop.colour)),
+                stroke,
+            ));
+        }
+
+        gradient.selected = gradient.selected.min(gradient.stops.len() - 1);
+        let num_stops = gradient.stops.len();
+        let stop = &mut gradient.stops[gradient.selected];
+        let mut delete = false;
+        ui.horizontal(|ui| {
+            changed |= srgb_edit_button(ui, &mut stop.colour);
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut stop.position)
+                        .range(0.0..=1.0)
+                        .speed(0.005),
+                )
+                .changed();
+            delete = ui
+                .add_enabled(num_stops > 2, egui::Button::new("Delete"))
+                .clicked();
+        });
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut gradient.interpolation, Interpolation::Srgb, "sRGB");
+            ui.radio_value(&mut gradient.interpolation, Interpolation::Oklab, "OKLab");
+            ui.radio_value(&mut gradient.interpolation, Interpolation::Oklch, "OKLCH");
+        });
+        if delete {
+            gradient.stops.remove(gradient.selected);
+            gradient.selected = gradient.selected.saturating_sub(1);
+            changed = true;
+        }
+        if changed {
+            gradient.sort();
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use super::Controller;
+use easy_shader_runner::{GraphicsContext, egui, wgpu};
+use glam::*;
+use shared::grid::Grid;
+use shared::orbit::{self, Pixel};
+use shared::push_constants::shader::*;
+use shared::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub struct Histogram {
+    /// Whether the outside was coloured by rank at the last update
+    pub enable: bool,
+    pub cycles: f32,
+    /// Colour values of the view sampled at evenly spaced ranks
+    pub quantiles: Vec<f32>,
+    /// Set when the view has changed since the quantiles were computed
+    pub stale: bool,
+    readback: Option<Readback>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            cycles: 3.0,
+            quantiles: vec![0.0; HISTOGRAM_SIZE as usize],
+            stale: true,
+            readback: None,
+        }
+    }
+}
+
+/// A copy of the orbit grids on its way back from the gpu, the ends followed by the features
+struct Readback {
+    buffer: wgpu::Buffer,
+    size: UVec2,
+    mapped: Arc<AtomicBool>,
+}
+
+impl Controller {
+    pub fn histogram_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Rank");
+            ui.add(
+                egui::DragValue::new(&mut self.histogram.cycles)
+                    .range(0.1..=50.0)
+                    .speed(0.05)
+                    .suffix(" cycles"),
+            );
+        });
+    }
+
+    /// Reads the orbit grids back once the view has finished iterating, and updates the
+    /// quantiles when they arrive a few frames later
+    pub fn update_histogram(&mut self, graphics_context: &GraphicsContext) {
+        let enable =
+            self.render_partitioning.shows(false) && self.outside.transfer == Transfer::Rank;
+        if enable && !self.histogram.enable {
+            self.histogram.stale = true;
+        }
+        self.histogram.enable = enable;
+        if !enable {
+            return;
+        }
+        let _ = graphics_context.device.poll(wgpu::PollType::Poll);
+        if let Some(readback) = &self.histogram.readback {
+            if readback.mapped.load(Ordering::Acquire) {
+                let readback = self.histogram.readback.take().unwrap();
+                self.finish_histogram_readback(readback);
+            }
+            return;
+        }
+        let is_iterating = self.cameras.mandelbrot.is_iterating()
+            || self.cameras.julia.is_iterating()
+            || self.time_slice.was_iterating;
+        if self.histogram.stale && !is_iterating {
+            self.histogram.stale = false;
+            self.start_histogram_readback(graphics_context);
+        }
+    }
+
+    fn start_histogram_readback(&mut self, graphics_context: &GraphicsContext) {
+        let device = &graphics_context.device;
+        let num_cells = (self.size.x * self.size.y).min(self.grid_cells) as wgpu::BufferAddress;
+        let ends_size = std::mem::size_of::<OrbitEnd>() as wgpu::BufferAddress * num_cells;
+        let features_size = std::mem::size_of::<OrbitFeatures>() as wgpu::BufferAddress * num_cells;
+        let sums_size = std::mem::size_of::<OrbitSums>() as wgpu::BufferAddress * num_cells;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("histogram_staging_buffer"),
+            size: ends_size + features_size + sums_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("histogram_readback_encoder"),
+        });
+        encoder.copy_buffer_to_buffer(self.grid_buffer.as_ref().unwrap(), 0, &buffer, 0, ends_size);
+        encoder.copy_buffer_to_buffer(
+            self.feature_grid_buffer.as_ref().unwrap(),
+            0,
+            &buffer,
+            ends_size,
+            features_size,
+        );
+        encoder.copy_buffer_to_buffer(
+            self.sum_grid_buffer.as_ref().unwrap(),
+            0,
+            &buffer,
+            ends_size + features_size,
+            sums_size,
+        );
+        graphics_context.queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(AtomicBool::new(false));
+        let mapped_clone = mapped.clone();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                result.unwrap();
+                mapped_clone.store(true, Ordering::Release);
+            });
+        self.histogram.readback = Some(Readback {
+            buffer,
+            size: self.size,
+            mapped,
+        });
+    }
+
+    fn finish_histogram_readback(&mut self, readback: Readback) {
+        let grid_size = readback.size;
+        let num_cells = (grid_size.x * grid_size.y).min(self.grid_cells) as usize;
+        let ends_size = std::mem::size_of::<OrbitEnd>() * num_cells;
+        let features_end = ends_size + std::mem::size_of::<OrbitFeatures>() * num_cells;
+        let (grid, feature_grid, sum_grid) = {
+            let bytes = readback.buffer.slice(..).get_mapped_range();
+            let grid = Grid::<OrbitEnd> {
+                size: grid_size,
+                buffer: bytemuck::pod_collect_to_vec(&bytes[..ends_size]),
+            };
+            let feature_grid = Grid::<OrbitFeatures> {
+                size: grid_size,
+                buffer: bytemuck::pod_collect_to_vec(&bytes[ends_size..features_end]),
+            };
+            let sum_grid = Grid::<OrbitSums> {
+                size: grid_size,
+                buffer: bytemuck::pod_collect_to_vec(&bytes[features_end..]),
+            };
+            (grid, feature_grid, sum_grid)
+        };
+        readback.buffer.unmap();
+        let constants = self.fragment_constants();
+        let newton = self.polynomial.newton();
+        let mut values = Vec::with_capacity((readback.size.x * readback.size.y) as usize);
+        for y in 0..readback.size.y {
+            for x in 0..readback.size.x {
+                let p = uvec2(x, y);
+                // Pixels past the end of the grids aren't kept
+                if !grid.as_ref().contains(p) {
+                    break;
+                }
+                let (end, features, sums) = (grid.get(p), feature_grid.get(p), sum_grid.get(p));
+                // Only the outside is equalised
+                if orbit::is_inside(&constants, end, features.i) {
+                    continue;
+                }
+                let pixel = Pixel::new(&constants, p.as_vec2() + 0.5);
+                let style = self.outside.style;
+                let x = orbit::render_parameters(
+                    &constants, &newton, style, pixel, end, features, sums,
+                )
+                .x;
+                if x.is_finite() {
+                    values.push(x);
+                }
+            }
+        }
+        if values.is_empty() {
+            return;
+        }
+        values.sort_unstable_by(f32::total_cmp);
+        let last = HISTOGRAM_SIZE as usize - 1;
+        for (k, quantile) in self.histogram.quantiles.iter_mut().enumerate() {
+            *quantile = values[k * (values.len() - 1) / last];
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use super::Controller;
+use super::ui::{palette_combo, style_combo, transfer_ui, wrap_combo};
+use easy_shader_runner::egui;
+use shared::push_constants::shader::*;
+use shared::*;
+
+const BLEND_MODES: [BlendMode; 5] = [
+    BlendMode::Normal,
+    BlendMode::Multiply,
+    BlendMode::Screen,
+    BlendMode::Overlay,
+    BlendMode::Add,
+];
+
+const REGIONS: [RenderPartitioning; 3] = [
+    RenderPartitioning::Outside,
+    RenderPartitioning::Inside,
+    RenderPartitioning::Both,
+];
+
+impl Controller {
+    /// The stack of layers blended over the base colouring, from the bottom up
+    pub fn layers_ui(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        let mut swap = None;
+        ui.collapsing(egui::RichText::new("Layers").size(15.0), |ui| {
+            let num_layers = self.layers.len();
+            for k in 0..num_layers {
+                let layer = &mut self.layers[k];
+                ui.horizontal(|ui| {
+                    ui.label(format!("Layer {}", k + 1));
+                    if ui.add_enabled(k > 0, egui::Button::new("Up")).clicked() {
+                        swap = Some(k - 1);
+                    }
+                    if ui
+                        .add_enabled(k + 1 < num_layers, egui::Button::new("Down"))
+                        .clicked()
+                    {
+                        swap = Some(k);
+                    }
+                    if ui.button("Remove").clicked() {
+                        remove = Some(k);
+                    }
+                });
+                egui::Grid::new(("layer_grid", k)).show(ui, |ui| {
+                    ui.label("Palette");
+                    palette_combo(
+                        ui,
+                        &format!("layer_palette_{k}"),
+                        &mut layer.colouring.palette,
+                    );
+                    ui.end_row();
+
+                    ui.label("Style");
+                    style_combo(ui, &format!("layer_style_{k}"), &mut layer.colouring.style);
+                    ui.end_row();
+
+                    ui.label("Region");
+                    egui::ComboBox::from_id_salt(("layer_region", k))
+                        .selected_text(format!("{:?}", layer.region))
+                        .show_ui(ui, |ui| {
+                            for region in REGIONS {
+                                ui.selectable_value(
+                                    &mut layer.region,
+                                    region,
+                                    format!("{region:?}"),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Blend");
+                    egui::ComboBox::from_id_salt(("layer_blend_mode", k))
+                        .selected_text(format!("{:?}", layer.blend_mode))
+                        .show_ui(ui, |ui| {
+                            for mode in BLEND_MODES {
+                                ui.selectable_value(
+                                    &mut layer.blend_mode,
+                                    mode,
+                                    format!("{mode:?}"),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Opacity");
+                    ui.add(egui::Slider::new(&mut layer.opacity, 0.0..=1.0));
+                    ui.end_row();
+
+                    ui.label("Period");
+                    ui.add(
+                        egui::DragValue::new(&mut layer.colouring.period)
+                            .range(0.01..=1.0)
+                            .speed(0.002),
+                    );
+                    ui.end_row();
+
+                    ui.label("Offset");
+                    ui.add(egui::DragValue::new(&mut layer.colouring.offset).speed(0.005));
+                    ui.end_row();
+
+                    ui.label("Transfer");
+                    transfer_ui(
+                        ui,
+                        &format!("layer_transfer_{k}"),
+                        &mut layer.colouring,
+                        false,
+                    );
+                    ui.end_row();
+
+                    ui.label("Wrap");
+                    wrap_combo(ui, &format!("layer_wrap_{k}"), &mut layer.colouring.wrap);
+                    ui.end_row();
+                });
+                ui.separator();
+            }
+            if ui
+                .add_enabled(num_layers < MAX_LAYERS, egui::Button::new("Add Layer"))
+                .clicked()
+            {
+                self.layers.push(Layer::default());
+            }
+        });
+        if let Some(k) = swap {
+            self.layers.swap(k, k + 1);
+        }
+        if let Some(k) = remove {
+            self.layers.remove(k);
+        }
+    }
+}
@@ -3,17 +3,35 @@ use crate::big_vec2::BigVec2;
 use dashu::float::FBig;
 use dashu::integer::IBig;
 use easy_shader_runner::{ControllerTrait, GraphicsContext, UiState, egui, wgpu, winit};
+#[cfg(not(target_arch = "wasm32"))]
+use export::*;
 use glam::*;
+use gradient::*;
+use histogram::*;
+use palette_file::*;
+use serde::{Deserialize, Serialize};
+use shared::formula::*;
 use shared::push_constants::shader::*;
 use shared::*;
 use std::collections::HashMap;
 use std::str::FromStr;
 use touch::*;
+use trap_image::*;
 use web_time::Instant;
 use winit::event::{ElementState, MouseButton, TouchPhase};
 
+mod cosine;
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
+mod gradient;
+mod histogram;
 mod keyboard;
+mod layers;
+mod newton;
+mod palette_file;
+mod session;
 mod touch;
+mod trap_image;
 mod ui;
 
 const MAX_ZOOM_MANDELBROT: f64 = 1e36;
@@ -21,6 +39,7 @@ const MAX_ZOOM_JULIA: f64 = 999999.9;
 const MAX_ITER_POINTS: u32 = 1307;
 const MAX_ADDITIONAL_ITERS: u32 = 200;
 const PRECISION: usize = 192;
+const MIN_ITERATIONS_PER_FRAME: f32 = 16.0;
 
 struct Cameras {
     mandelbrot: Camera,
@@ -50,6 +69,7 @@ struct Camera {
     translate: BigVec2,
     grabbing: bool,
     needs_reiterate: bool,
+    remaining_iterations: u32,
 }
 
 impl Camera {
@@ -59,9 +79,14 @@ impl Camera {
             translate,
             grabbing: false,
             needs_reiterate: true,
+            remaining_iterations: 0,
         }
     }
 
+    fn is_iterating(&self) -> bool {
+        self.needs_reiterate || self.remaining_iterations > 0
+    }
+
     #[allow(dead_code)]
     fn deep_mandelbrot() -> Self {
         Self::new(
@@ -117,6 +142,26 @@ impl Default for MarkerIterations {
     }
 }
 
+struct Trap {
+    shape: TrapShape,
+    position: BigVec2,
+    size: f32,
+    angle: f32,
+    dragging: bool,
+}
+
+impl Default for Trap {
+    fn default() -> Self {
+        Self {
+            shape: TrapShape::default(),
+            position: BigVec2::from_f64s(0.0, 0.0).with_precision(PRECISION),
+            size: 0.5,
+            angle: 0.0,
+            dragging: false,
+        }
+    }
+}
+
 struct RenderSplit {
     value: f64,
     dragging: Option<egui::CursorIcon>,
@@ -131,6 +176,7 @@ impl Default for RenderSplit {
     }
 }
 
+#[derive(Clone, Copy, Serialize, Deserialize)]
 struct Smooth {
     enable: bool,
     value: f32,
@@ -151,6 +197,55 @@ impl Smooth {
     }
 }
 
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct SlopeShading {
+    enable: bool,
+    light: Light,
+}
+
+impl Default for SlopeShading {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            light: Light {
+                angle: std::f32::consts::FRAC_PI_4,
+                elevation: 1.0,
+                intensity: 0.8,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Stripe {
+    density: f32,
+    skip: u32,
+}
+
+impl Default for Stripe {
+    fn default() -> Self {
+        Self {
+            density: 5.0,
+            skip: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct FieldLines {
+    enable: bool,
+    width: f32,
+}
+
+impl Default for FieldLines {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            width: 0.1,
+        }
+    }
+}
+
 struct Animate {
     enable: bool,
     value: f32,
@@ -181,6 +276,52 @@ impl Animate {
     }
 }
 
+struct TimeSlice {
+    enable: bool,
+    budget_ms: f32,
+    iterations_per_frame: f32,
+    last_instant: Instant,
+    was_iterating: bool,
+}
+
+impl Default for TimeSlice {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            budget_ms: 25.0,
+            iterations_per_frame: 1000.0,
+            last_instant: Instant::now(),
+            was_iterating: false,
+        }
+    }
+}
+
+impl TimeSlice {
+    /// Scales the number of iterations per frame so that frames take about `budget_ms`
+    fn tick(&mut self, is_iterating: bool) {
+        let elapsed_ms = self.last_instant.elapsed().as_secs_f32() * 1000.0;
+        self.last_instant = Instant::now();
+        if self.was_iterating {
+            let ratio = (self.budget_ms / elapsed_ms).clamp(0.8, 1.25);
+            self.iterations_per_frame =
+                (self.iterations_per_frame * ratio).clamp(MIN_ITERATIONS_PER_FRAME, 1e6);
+        }
+        self.was_iterating = is_iterating;
+    }
+
+    fn is_enabled(&self, size: UVec2) -> bool {
+        self.enable && size.x * size.y <= MAX_TIME_SLICED_PIXELS
+    }
+
+    fn iterations_per_frame(&self, size: UVec2) -> u32 {
+        if self.is_enabled(size) {
+            self.iterations_per_frame as u32
+        } else {
+            u32::MAX
+        }
+    }
+}
+
 struct MandelbrotReference {
     buffer: Option<wgpu::Buffer>,
     points: Vec<Vec2>,
@@ -300,20 +441,47 @@ pub struct Controller {
     context_menu: Option<DVec2>,
     render_julia_set: bool,
     render_split: RenderSplit,
-    palette: Palette,
-    palette_period: f32,
+    outside: Colouring,
+    inside: Colouring,
+    /// Drawn over the base colourings in order, at most `MAX_LAYERS` of them
+    layers: Vec<Layer>,
+    gradient: Gradient,
+    cosine_palette: CosinePalette,
+    dither: Dither,
+    palette_file: PaletteFile,
     smooth: Smooth,
     animate: Animate,
+    time_slice: TimeSlice,
     show_fps: bool,
-    render_style: RenderStyle,
+    distance_estimate_thickness: f32,
+    slope_shading: SlopeShading,
+    stripe: Stripe,
+    field_lines: FieldLines,
+    orbit_trap: Trap,
+    trap_image: TrapImage,
+    histogram: Histogram,
     mandelbrot_reference: MandelbrotReference,
     render_partitioning: RenderPartitioning,
     delta_params: DeltaParams,
+    formula: Formula,
+    polynomial: newton::Polynomial,
     exponent: f64,
+    exponent_im: f64,
+    branch: Branch,
     escape_radius: f32,
     iteration_mode: IterationMode,
     ctrl_down: bool,
     touches: HashMap<u64, Touch>,
+    session_status: Option<String>,
+    /// Pixels the grids were allocated for, row after row of the window. A window that has
+    /// grown past them has the rest of its pixels iterated again every frame
+    grid_cells: u32,
+    grid_buffer: Option<wgpu::Buffer>,
+    feature_grid_buffer: Option<wgpu::Buffer>,
+    sum_grid_buffer: Option<wgpu::Buffer>,
+    colourings_buffer: Option<wgpu::Buffer>,
+    #[cfg(not(target_arch = "wasm32"))]
+    palette_cycle_export: PaletteCycleExport,
     #[cfg(target_arch = "wasm32")]
     wasm_stuff: WasmStuff,
 }
@@ -353,20 +521,47 @@ impl Controller {
             context_menu: None,
             render_julia_set: false,
             render_split: RenderSplit::default(),
-            palette: Palette::default(),
-            palette_period: 0.5,
+            outside: Colouring::default(),
+            inside: Colouring {
+                style: RenderStyle::Period,
+                ..Default::default()
+            },
+            layers: vec![],
+            gradient: Gradient::default(),
+            cosine_palette: CosinePalette::default(),
+            dither: Dither::default(),
+            palette_file: PaletteFile::default(),
             smooth: Smooth::default(),
             animate: Animate::default(),
+            time_slice: TimeSlice::default(),
             show_fps: false,
-            render_style: RenderStyle::default(),
+            distance_estimate_thickness: 2.0,
+            slope_shading: SlopeShading::default(),
+            stripe: Stripe::default(),
+            field_lines: FieldLines::default(),
+            orbit_trap: Trap::default(),
+            trap_image: TrapImage::default(),
+            histogram: Histogram::default(),
             mandelbrot_reference: MandelbrotReference::default(),
             render_partitioning: RenderPartitioning::default(),
             delta_params: DeltaParams::default(),
+            formula: Formula::default(),
+            polynomial: newton::Polynomial::default(),
             exponent: 2.0,
+            exponent_im: 0.0,
+            branch: Branch::default(),
             escape_radius: 2.0,
             iteration_mode: IterationMode::default(),
             ctrl_down: false,
             touches: HashMap::new(),
+            session_status: None,
+            grid_cells: 0,
+            grid_buffer: None,
+            feature_grid_buffer: None,
+            sum_grid_buffer: None,
+            colourings_buffer: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            palette_cycle_export: PaletteCycleExport::default(),
             #[cfg(target_arch = "wasm32")]
             wasm_stuff: WasmStuff::default(),
         }
@@ -417,6 +612,56 @@ impl Controller {
                 < MARKER_RADIUS as f64 * MARKER_RADIUS as f64
     }
 
+    fn can_grab_orbit_trap(&self) -> bool {
+        self.pos_on_orbit_trap(self.cursor)
+    }
+
+    /// The colourings of the regions and layers that are shown
+    fn shown_colourings(&self) -> impl Iterator<Item = &Colouring> {
+        let shows = |region: RenderPartitioning| {
+            [false, true]
+                .into_iter()
+                .any(|inside| self.render_partitioning.shows(inside) && region.shows(inside))
+        };
+        [
+            (RenderPartitioning::Outside, &self.outside),
+            (RenderPartitioning::Inside, &self.inside),
+        ]
+        .into_iter()
+        .chain(
+            self.layers
+                .iter()
+                .map(|layer| (layer.region, &layer.colouring)),
+        )
+        .filter(move |(region, _)| shows(*region))
+        .map(|(_, colouring)| colouring)
+    }
+
+    fn uses_style(&self, style: RenderStyle) -> bool {
+        self.shown_colourings()
+            .any(|colouring| colouring.style == style)
+    }
+
+    fn uses_palette(&self, palette: Palette) -> bool {
+        self.shown_colourings()
+            .any(|colouring| colouring.palette == palette)
+    }
+
+    fn complex_exponent(&self) -> Exponent {
+        Exponent {
+            re: self.exponent as f32,
+            im: self.exponent_im as f32,
+            branch: self.branch,
+        }
+    }
+
+    fn pos_on_orbit_trap(&self, pos: DVec2) -> bool {
+        (self.uses_style(RenderStyle::OrbitTrap) || self.uses_style(RenderStyle::ImageTrap))
+            && !self.is_pos_in_julia(pos)
+            && pos.distance_squared(self.to_screen_space_big(&self.orbit_trap.position))
+                < MARKER_RADIUS as f64 * MARKER_RADIUS as f64
+    }
+
     fn is_pos_in_julia(&self, pos: DVec2) -> bool {
         let size = self.size.as_dvec2();
         let is_split_vertical = size.x > size.y;
@@ -460,6 +705,88 @@ impl Controller {
         self.num_iterations
             .calculate_num_iterations(self.cameras.mandelbrot.zoom)
     }
+
+    fn fragment_constants(&self) -> FragmentConstants {
+        FragmentConstants {
+            size: self.size.into(),
+            time: self.start.elapsed().as_secs_f32(),
+            mandelbrot_camera_translate: self.cameras.mandelbrot.translate.as_vec2(),
+            mandelbrot_camera_zoom: self.cameras.mandelbrot.zoom as f32,
+            julia_camera_translate: self.cameras.julia.translate.as_vec2(),
+            julia_camera_zoom: self.cameras.julia.zoom as f32,
+            num_iterations: self.calculate_num_iterations() as f32,
+            show_iterations: (self.marker_iterations.enabled
+                && !self.marker_iterations.points.is_empty())
+            .into(),
+            num_points: self.marker_iterations.points.len() as u32,
+            marker: self.marker_iterations.position.as_vec2(),
+            marker_screen_space: self
+                .to_screen_space_big(&self.marker_iterations.position)
+                .as_vec2(),
+            render_julia_set: self.render_julia_set.into(),
+            render_split: self.render_split.value as f32,
+            smooth_factor: self.smooth.factor(),
+            animate_time: self.animate.value,
+            mandelbrot_num_ref_iterations: self.mandelbrot_reference.num_ref_iterations,
+            needs_reiterate_mandelbrot: self.cameras.mandelbrot.needs_reiterate.into(),
+            needs_reiterate_julia: self.cameras.julia.needs_reiterate.into(),
+            resume_mandelbrot: (self.cameras.mandelbrot.remaining_iterations > 0).into(),
+            resume_julia: (self.cameras.julia.remaining_iterations > 0).into(),
+            time_sliced: self.time_slice.is_enabled(self.size).into(),
+            iterations_per_frame: self.time_slice.iterations_per_frame(self.size),
+            iteration_mode: self.iteration_mode,
+            render_partitioning: self.render_partitioning,
+            exponent: self.complex_exponent(),
+            escape_radius: self.escape_radius,
+            distance_estimate_thickness: self.distance_estimate_thickness,
+            slope_shading: self.slope_shading.enable.into(),
+            light: self.slope_shading.light,
+            stripe_density: self.stripe.density,
+            stripe_skip: self.stripe.skip,
+            orbit_trap: OrbitTrap {
+                position: self.orbit_trap.position.as_vec2().to_array(),
+                shape: self.orbit_trap.shape,
+                size: self.orbit_trap.size,
+                angle: self.orbit_trap.angle,
+            },
+            orbit_trap_screen_space: self
+                .to_screen_space_big(&self.orbit_trap.position)
+                .as_vec2()
+                .to_array(),
+            image_trap: ImageTrap {
+                aspect: self.trap_image.aspect,
+                mode: self.trap_image.mode,
+                opacity: self.trap_image.opacity,
+            },
+            histogram_cycles: self.histogram.cycles,
+            field_lines: self.field_lines.enable.into(),
+            field_line_width: self.field_lines.width,
+            dither: self.dither,
+            formula: self.formula,
+        }
+    }
+
+    fn colourings(&self) -> Colourings {
+        let mut layers = [Layer::default(); MAX_LAYERS];
+        layers[..self.layers.len()].copy_from_slice(&self.layers);
+        let stops = &self.gradient.sorted_stops;
+        let mut gradient = [GradientStop::default(); MAX_GRADIENT_STOPS as usize];
+        gradient[..stops.len()].copy_from_slice(stops);
+        let mut histogram = [0.0; HISTOGRAM_SIZE as usize];
+        histogram.copy_from_slice(&self.histogram.quantiles);
+        Colourings {
+            outside: self.outside,
+            inside: self.inside,
+            layers,
+            num_layers: self.layers.len() as u32,
+            cosine_palette: self.cosine_palette,
+            newton: self.polynomial.newton(),
+            gradient,
+            num_gradient_stops: stops.len() as u32,
+            gradient_interpolation: self.gradient.interpolation,
+            histogram,
+        }
+    }
 }
 
 impl ControllerTrait for Controller {
@@ -476,11 +803,18 @@ impl ControllerTrait for Controller {
     fn mouse_move(&mut self, position: DVec2) {
         let prev_cursor = self.cursor;
         self.cursor = position;
-        if self.marker_iterations.dragging {
+        if let Some(k) = self.polynomial.dragging {
+            self.drag_root(k, prev_cursor, self.cursor);
+        } else if self.marker_iterations.dragging {
             self.marker_iterations.position +=
                 self.to_uv_space_big(self.cursor) - self.to_uv_space_big(prev_cursor);
             self.marker_iterations.recompute = self.marker_iterations.enabled;
             self.cameras.julia.needs_reiterate = true;
+        } else if self.orbit_trap.dragging {
+            self.orbit_trap.position +=
+                self.to_uv_space_big(self.cursor) - self.to_uv_space_big(prev_cursor);
+            self.cameras.mandelbrot.needs_reiterate = true;
+            self.cameras.julia.needs_reiterate = true;
         } else if self.render_split.dragging.is_some() {
             let size = self.size.as_dvec2();
             let delta = (prev_cursor - self.cursor) / size;
@@ -539,11 +873,18 @@ impl ControllerTrait for Controller {
             << match button {
                 MouseButton::Left => {
                     if matches!(state, ElementState::Pressed) {
-                        self.marker_iterations.dragging = self.can_grab_marker();
+                        self.polynomial.dragging = self.can_grab_root();
+                        self.marker_iterations.dragging =
+                            self.polynomial.dragging.is_none() && self.can_grab_marker();
+                        self.orbit_trap.dragging = self.polynomial.dragging.is_none()
+                            && !self.marker_iterations.dragging
+                            && self.can_grab_orbit_trap();
                         self.render_split.dragging = self.can_grab_render_split();
                         self.camera().grabbing = true;
                     } else {
+                        self.polynomial.dragging = None;
                         self.marker_iterations.dragging = false;
+                        self.orbit_trap.dragging = false;
                         self.render_split.dragging = None;
                         for camera in self.cameras.iter_mut() {
                             camera.grabbing = false;
@@ -571,45 +912,33 @@ impl ControllerTrait for Controller {
 
     fn prepare_render(
         &mut self,
-        _gfx_ctx: &GraphicsContext,
+        gfx_ctx: &GraphicsContext,
         _offset: Vec2,
     ) -> impl bytemuck::NoUninit {
         self.animate.tick();
-        let needs_reiterate_mandelbrot = self.cameras.mandelbrot.needs_reiterate;
-        let needs_reiterate_julia = self.cameras.julia.needs_reiterate;
-        self.cameras.mandelbrot.needs_reiterate = false;
-        self.cameras.julia.needs_reiterate = false;
-        FragmentConstants {
-            size: self.size.into(),
-            time: self.start.elapsed().as_secs_f32(),
-            mandelbrot_camera_translate: self.cameras.mandelbrot.translate.as_vec2(),
-            mandelbrot_camera_zoom: self.cameras.mandelbrot.zoom as f32,
-            julia_camera_translate: self.cameras.julia.translate.as_vec2(),
-            julia_camera_zoom: self.cameras.julia.zoom as f32,
-            num_iterations: self.calculate_num_iterations() as f32,
-            show_iterations: (self.marker_iterations.enabled
-                && !self.marker_iterations.points.is_empty())
-            .into(),
-            num_points: self.marker_iterations.points.len() as u32,
-            marker: self.marker_iterations.position.as_vec2(),
-            marker_screen_space: self
-                .to_screen_space_big(&self.marker_iterations.position)
-                .as_vec2(),
-            render_julia_set: self.render_julia_set.into(),
-            render_split: self.render_split.value as f32,
-            palette: self.palette,
-            smooth_factor: self.smooth.factor(),
-            animate_time: self.animate.value,
-            palette_period: self.palette_period,
-            render_style: self.render_style,
-            mandelbrot_num_ref_iterations: self.mandelbrot_reference.num_ref_iterations,
-            needs_reiterate_mandelbrot: needs_reiterate_mandelbrot.into(),
-            needs_reiterate_julia: needs_reiterate_julia.into(),
-            iteration_mode: self.iteration_mode,
-            render_partitioning: self.render_partitioning,
-            exponent: self.exponent as f32,
-            escape_radius: self.escape_radius,
+        self.time_slice
+            .tick(self.cameras.mandelbrot.is_iterating() || self.cameras.julia.is_iterating());
+        let num_iterations = self.calculate_num_iterations() as u32 + 1;
+        for camera in self.cameras.iter_mut() {
+            if camera.needs_reiterate {
+                camera.remaining_iterations = num_iterations;
+                self.histogram.stale = true;
+            }
+        }
+        let constants = self.fragment_constants();
+        // What doesn't fit in the push constants is small enough to write every frame
+        gfx_ctx.queue.write_buffer(
+            self.colourings_buffer.as_ref().unwrap(),
+            0,
+            bytemuck::bytes_of(&self.colourings()),
+        );
+        for camera in self.cameras.iter_mut() {
+            camera.needs_reiterate = false;
+            camera.remaining_iterations = camera
+                .remaining_iterations
+                .saturating_sub(constants.iterations_per_frame);
         }
+        constants
     }
 
     fn describe_bind_groups(
@@ -617,42 +946,124 @@ impl ControllerTrait for Controller {
         gfx_ctx: &GraphicsContext,
     ) -> (Vec<wgpu::BindGroupLayout>, Vec<wgpu::BindGroup>) {
         let device = &gfx_ctx.device;
-        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+        let entries = [
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: false },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
-            ],
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 8,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+        let num_storage_buffers = entries
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    entry.ty,
+                    wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { .. },
+                        ..
+                    }
+                )
+            })
+            .count();
+        // Browsers allow 8 storage buffers per shader stage, and the constants take one of them
+        // where they are emulated
+        assert!(num_storage_buffers < 8);
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
             label: Some("bind_group_layout"),
         });
 
+        // The bind group is only described once, so the grids are sized for the window as it is
+        // now, or for the largest one allowed while its size isn't known yet
+        let max_grid_cells = GRID_SIZE.x * GRID_SIZE.y;
+        let num_pixels = self.size.x * self.size.y;
+        self.grid_cells = if num_pixels > 0 {
+            num_pixels.min(max_grid_cells)
+        } else {
+            max_grid_cells
+        };
+        let grid_cells = self.grid_cells as usize;
         use wgpu::util::DeviceExt;
         let marker_iteration_points_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -666,12 +1077,61 @@ impl ControllerTrait for Controller {
                 usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
                 contents: &[0; std::mem::size_of::<Vec2>() * MAX_ITER_POINTS as usize],
             });
-        let render_params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("fading_particles_buffer"),
+        let orbit_ends_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("orbit_ends_buffer"),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            size: (std::mem::size_of::<OrbitEnd>() * grid_cells) as wgpu::BufferAddress,
+            mapped_at_creation: false,
+        });
+        let orbit_features_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("orbit_features_buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            size: (std::mem::size_of::<OrbitFeatures>() * grid_cells) as wgpu::BufferAddress,
+            mapped_at_creation: false,
+        });
+        let orbit_sums_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("orbit_sums_buffer"),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            size: (std::mem::size_of::<OrbitSums>() * grid_cells) as wgpu::BufferAddress,
+            mapped_at_creation: false,
+        });
+        let iteration_states_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("iteration_states_buffer"),
+            usage: wgpu::BufferUsages::STORAGE,
+            size: (std::mem::size_of::<IterationState>()
+                * grid_cells.min(MAX_TIME_SLICED_PIXELS as usize))
+                as wgpu::BufferAddress,
+            mapped_at_creation: false,
+        });
+        let colourings_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("colourings_buffer"),
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            contents: &[0; std::mem::size_of::<RenderParameters>()
-                * GRID_SIZE.x as usize
-                * GRID_SIZE.y as usize],
+            size: std::mem::size_of::<Colourings>() as wgpu::BufferAddress,
+            mapped_at_creation: false,
+        });
+        let trap_image_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("trap_image_texture"),
+            size: wgpu::Extent3d {
+                width: TRAP_IMAGE_SIZE,
+                height: TRAP_IMAGE_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let trap_image_view =
+            trap_image_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let trap_image_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("trap_image_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -687,7 +1147,31 @@ impl ControllerTrait for Controller {
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: render_params_buffer.as_entire_binding(),
+                    resource: orbit_ends_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: iteration_states_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&trap_image_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&trap_image_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: colourings_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: orbit_features_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: orbit_sums_buffer.as_entire_binding(),
                 },
             ],
             label: Some("particles_bind_group"),
@@ -695,6 +1179,12 @@ impl ControllerTrait for Controller {
 
         self.marker_iterations.points_buffer = Some(marker_iteration_points_buffer);
         self.mandelbrot_reference.buffer = Some(perturbation_points_buffer);
+        self.trap_image.texture = Some(trap_image_texture);
+        self.update_trap_image(gfx_ctx);
+        self.grid_buffer = Some(orbit_ends_buffer);
+        self.feature_grid_buffer = Some(orbit_features_buffer);
+        self.sum_grid_buffer = Some(orbit_sums_buffer);
+        self.colourings_buffer = Some(colourings_buffer);
 
         (vec![layout], vec![bind_group])
     }
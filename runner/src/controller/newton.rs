@@ -0,0 +1,108 @@
+use super::{Controller, PRECISION};
+use crate::big_vec2::BigVec2;
+use easy_shader_runner::egui;
+use glam::*;
+use shared::formula::*;
+use shared::*;
+
+/// The polynomial of the Newton fractal, given by roots that are dragged around like the marker
+pub struct Polynomial {
+    pub roots: Vec<BigVec2>,
+    pub relaxation: f32,
+    /// Index of the root being dragged
+    pub dragging: Option<usize>,
+}
+
+impl Default for Polynomial {
+    fn default() -> Self {
+        let newton = Newton::default();
+        Self {
+            roots: newton.roots[..newton.num_roots as usize]
+                .iter()
+                .map(|root| {
+                    BigVec2::from_f64s(root[0] as f64, root[1] as f64).with_precision(PRECISION)
+                })
+                .collect(),
+            relaxation: newton.relaxation,
+            dragging: None,
+        }
+    }
+}
+
+impl Polynomial {
+    pub fn newton(&self) -> Newton {
+        let mut roots = [[0.0; 2]; MAX_ROOTS];
+        for (root, position) in roots.iter_mut().zip(&self.roots) {
+            *root = position.as_vec2().to_array();
+        }
+        Newton {
+            roots,
+            num_roots: self.roots.len() as u32,
+            relaxation: self.relaxation,
+        }
+    }
+}
+
+impl Controller {
+    pub fn can_grab_root(&self) -> Option<usize> {
+        self.pos_on_root(self.cursor)
+    }
+
+    /// The root drawn under `pos`, if the roots are shown
+    pub fn pos_on_root(&self, pos: DVec2) -> Option<usize> {
+        if !self.formula.is_root_finding() || self.is_pos_in_julia(pos) {
+            return None;
+        }
+        self.polynomial.roots.iter().position(|root| {
+            pos.distance_squared(self.to_screen_space_big(root))
+                < MARKER_RADIUS as f64 * MARKER_RADIUS as f64
+        })
+    }
+
+    /// Moves the root being dragged by the cursor's movement from `prev_pos` to `pos`
+    pub fn drag_root(&mut self, k: usize, prev_pos: DVec2, pos: DVec2) {
+        let delta = self.to_uv_space_big(pos) - self.to_uv_space_big(prev_pos);
+        self.polynomial.roots[k] += delta;
+        self.roots_changed();
+    }
+
+    fn roots_changed(&mut self) {
+        self.marker_iterations.recompute = self.marker_iterations.enabled;
+        self.cameras.mandelbrot.needs_reiterate = true;
+        self.cameras.julia.needs_reiterate = true;
+    }
+
+    /// The roots of the polynomial and the relaxation of Newton's method
+    pub fn newton_ui(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(egui::RichText::new("Roots").size(14.0));
+        });
+        let num_roots = self.polynomial.roots.len();
+        ui.horizontal(|ui| {
+            ui.label(format!("{num_roots} roots"));
+            if ui
+                .add_enabled(num_roots < MAX_ROOTS, egui::Button::new("Add"))
+                .clicked()
+            {
+                // New roots start at the center of the view
+                let root = self.cameras.mandelbrot.translate.clone();
+                self.polynomial.roots.push(root);
+                self.roots_changed();
+            }
+            if ui
+                .add_enabled(num_roots > 2, egui::Button::new("Remove"))
+                .clicked()
+            {
+                self.polynomial.roots.pop();
+                self.roots_changed();
+            }
+        });
+        ui.label("Drag the roots to move them");
+        if ui
+            .add(egui::Slider::new(&mut self.polynomial.relaxation, 0.1..=2.0).text("Relaxation"))
+            .changed()
+        {
+            self.roots_changed();
+        }
+    }
+}
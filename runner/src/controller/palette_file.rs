@@ -0,0 +1,501 @@
+use super::Controller;
+use easy_shader_runner::egui;
+use glam::*;
+use shared::colour::get_col;
+use shared::push_constants::shader::*;
+#[cfg(target_arch = "wasm32")]
+use std::sync::{Arc, Mutex};
+
+/// Number of colours in a Fractint map
+const MAP_SIZE: usize = 256;
+/// Ultra Fractal gradients repeat every 400 indices
+const UGR_PERIOD: f32 = 400.0;
+/// Stops sampled from the cosine palettes when exporting them as gradients
+const EXPORT_STOPS: usize = 64;
+
+#[derive(Default)]
+pub struct PaletteFile {
+    /// Name and contents of the file picked by the user, filled in once the browser has read it
+    #[cfg(target_arch = "wasm32")]
+    picked_file: Arc<Mutex<Option<(String, Vec<u8>)>>>,
+    status: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PaletteFormat {
+    /// Fractint `.map`
+    Map,
+    /// GIMP `.ggr`
+    Ggr,
+    /// Ultra Fractal `.ugr`
+    Ugr,
+}
+
+impl PaletteFormat {
+    const EXTENSIONS: [&str; 3] = ["map", "ggr", "ugr"];
+
+    fn from_file_name(name: &str) -> Result<Self, String> {
+        let extension = name.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+        match extension.as_str() {
+            "map" => Ok(Self::Map),
+            "ggr" => Ok(Self::Ggr),
+            "ugr" => Ok(Self::Ugr),
+            _ => Err(format!("unknown palette format: {name}")),
+        }
+    }
+}
+
+impl Controller {
+    pub fn palette_file_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Import").clicked() {
+                self.pick_palette_file();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if ui.button("Export").clicked() {
+                self.export_palette_file();
+            }
+        });
+        if let Some(status) = &self.palette_file.status {
+            ui.label(status);
+        }
+    }
+
+    fn pick_palette_file(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Palette", &PaletteFormat::EXTENSIONS)
+            .pick_file()
+        {
+            let name = path.display().to_string();
+            match std::fs::read(&path) {
+                Ok(bytes) => self.import_palette(&name, &bytes),
+                Err(err) => {
+                    self.palette_file.status = Some(format!("Failed to read {name}: {err}"))
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let picked_file = self.palette_file.picked_file.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(file) = rfd::AsyncFileDialog::new()
+                    .add_filter("Palette", &PaletteFormat::EXTENSIONS)
+                    .pick_file()
+                    .await
+                {
+                    *picked_file.lock().unwrap() = Some((file.file_name(), file.read().await));
+                }
+            });
+        }
+    }
+
+    /// Imports a palette picked in the browser once its contents have arrived
+    #[cfg(target_arch = "wasm32")]
+    pub fn update_palette_file(&mut self) {
+        let picked_file = self.palette_file.picked_file.lock().unwrap().take();
+        if let Some((name, bytes)) = picked_file {
+            self.import_palette(&name, &bytes);
+        }
+    }
+
+    fn import_palette(&mut self, name: &str, bytes: &[u8]) {
+        let result = PaletteFormat::from_file_name(name).and_then(|format| {
+            let text = String::from_utf8_lossy(bytes);
+            match format {
+                PaletteFormat::Map => parse_map(&text).map(|stops| (stops, 0)),
+                PaletteFormat::Ggr => parse_ggr(&text),
+                PaletteFormat::Ugr => parse_ugr(&text).map(|stops| (stops, 0)),
+            }
+        });
+        self.palette_file.status = Some(match result {
+            Ok((stops, approximated)) => {
+                let mut status = if stops.len() > MAX_GRADIENT_STOPS as usize {
+                    format!(
+                        "Imported the first {MAX_GRADIENT_STOPS} of {} stops, \
+                         the rest were dropped",
+                        stops.len()
+                    )
+                } else {
+                    format!("Imported {} stops", stops.len())
+                };
+                if approximated > 0 {
+                    status += &format!(
+                        ". {approximated} segments use a blend or colour model other than \
+                         linear rgb, and were approximated by one"
+                    );
+                }
+                self.gradient.set_stops(stops);
+                self.outside.palette = Palette::Custom;
+                status
+            }
+            Err(err) => format!("Failed to import {name}: {err}"),
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_palette_file(&mut self) {
+        let name = format!("{:?}", self.outside.palette);
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Fractint Map", &["map"])
+            .add_filter("GIMP Gradient", &["ggr"])
+            .add_filter("Ultra Fractal Gradient", &["ugr"])
+            .set_file_name(format!("{name}.map"))
+            .save_file()
+        else {
+            return;
+        };
+        let path_name = path.display().to_string();
+        let result = PaletteFormat::from_file_name(&path_name).and_then(|format| {
+            let text = match format {
+                PaletteFormat::Map => write_map(|t| self.sample_palette(t)),
+                PaletteFormat::Ggr => write_ggr(&name, &self.palette_stops()),
+                PaletteFormat::Ugr => write_ugr(&name, &self.palette_stops()),
+            };
+            std::fs::write(&path, text).map_err(|err| err.to_string())
+        });
+        self.palette_file.status = Some(match result {
+            Ok(()) => format!("Exported {path_name}"),
+            Err(err) => format!("Failed to export {path_name}: {err}"),
+        });
+    }
+
+    /// Colour of the outside palette, with `t` from 0 to 1 covering one period
+    fn sample_palette(&self, t: f32) -> Vec3 {
+        let palette = self.outside.palette;
+        get_col(palette, &self.colourings(), t * palette.period())
+    }
+
+    /// The outside palette as stops covering 0 to 1, with the colour at 0 repeated at 1.
+    /// Custom gradients are exported exactly, cosine palettes are sampled
+    fn palette_stops(&self) -> Vec<GradientStop> {
+        let mut stops = if self.outside.palette == Palette::Custom {
+            self.gradient.sorted_stops.clone()
+        } else {
+            (0..EXPORT_STOPS)
+                .map(|k| {
+                    let position = k as f32 / EXPORT_STOPS as f32;
+                    GradientStop {
+                        colour: self.sample_palette(position).to_array(),
+                        position,
+                    }
+                })
+                .collect()
+        };
+        let wrap = self.sample_palette(0.0).to_array();
+        if stops.first().is_none_or(|stop| stop.position > 0.0) {
+            stops.insert(
+                0,
+                GradientStop {
+                    colour: wrap,
+                    position: 0.0,
+                },
+            );
+        }
+        if stops.last().is_none_or(|stop| stop.position < 1.0) {
+            stops.push(GradientStop {
+                colour: wrap,
+                position: 1.0,
+            });
+        }
+        stops
+    }
+}
+
+fn parse_u8(s: &str) -> Result<u8, String> {
+    s.parse()
+        .map_err(|_| format!("invalid colour component: {s}"))
+}
+
+fn parse_f32(s: &str) -> Result<f32, String> {
+    s.parse().map_err(|_| format!("invalid number: {s}"))
+}
+
+/// A Fractint map is a line of red, green and blue from 0 to 255 for each colour,
+/// optionally followed by a comment
+fn parse_map(text: &str) -> Result<Vec<GradientStop>, String> {
+    let mut colours = vec![];
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+        let Some(r) = tokens.next() else {
+            continue;
+        };
+        let (Some(g), Some(b)) = (tokens.next(), tokens.next()) else {
+            return Err(format!("expected three components: {line}"));
+        };
+        let rgb = uvec3(
+            parse_u8(r)? as u32,
+            parse_u8(g)? as u32,
+            parse_u8(b)? as u32,
+        );
+        colours.push(rgb.as_vec3() / 255.0);
+    }
+    if colours.is_empty() {
+        return Err("no colours".to_string());
+    }
+    let n = colours.len() as f32;
+    Ok(colours
+        .into_iter()
+        .enumerate()
+        .map(|(k, colour)| GradientStop {
+            colour: colour.to_array(),
+            position: k as f32 / n,
+        })
+        .collect())
+}
+
+/// A GIMP gradient is a list of segments, each blending between a left and right colour.
+/// Every segment is approximated by a linear blend in rgb, through its midpoint. Also returns
+/// how many segments were curved, sine, spherical, stepped or blended in hsv, as those only
+/// come out approximately
+fn parse_ggr(text: &str) -> Result<(Vec<GradientStop>, usize), String> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("GIMP Gradient") {
+        return Err("missing GIMP Gradient header".to_string());
+    }
+    let mut lines = lines.filter(|line| !line.trim().is_empty()).peekable();
+    if lines.peek().is_some_and(|line| line.starts_with("Name:")) {
+        lines.next();
+    }
+    let num_segments: usize = lines
+        .next()
+        .and_then(|line| line.trim().parse().ok())
+        .ok_or("missing number of segments")?;
+
+    let mut stops: Vec<GradientStop> = vec![];
+    let mut approximated = 0;
+    for line in lines.take(num_segments) {
+        let mut tokens = line.split_whitespace();
+        let values = tokens
+            .by_ref()
+            .take(11)
+            .map(parse_f32)
+            .collect::<Result<Vec<_>, _>>()?;
+        let [left, mid, right, r0, g0, b0, _, r1, g1, b1, _] = values[..] else {
+            return Err(format!("incomplete segment: {line}"));
+        };
+        // The blend function and colour model, zero for linear and rgb. Older files leave
+        // them out
+        let blend = tokens.next().unwrap_or("0");
+        let colour_model = tokens.next().unwrap_or("0");
+        if blend != "0" || colour_model != "0" {
+            approximated += 1;
+        }
+        let left_colour = vec3(r0, g0, b0);
+        let right_colour = vec3(r1, g1, b1);
+        // Segments that meet with the same colour share a stop
+        if stops
+            .last()
+            .is_none_or(|stop| stop.position != left || stop.colour != left_colour.to_array())
+        {
+            stops.push(GradientStop {
+                colour: left_colour.to_array(),
+                position: left,
+            });
+        }
+        if (mid - 0.5 * (left + right)).abs() > 1e-3 {
+            stops.push(GradientStop {
+                colour: left_colour.lerp(right_colour, 0.5).to_array(),
+                position: mid,
+            });
+        }
+        stops.push(GradientStop {
+            colour: right_colour.to_array(),
+            position: right,
+        });
+    }
+    if stops.is_empty() {
+        return Err("no segments".to_string());
+    }
+    Ok((stops, approximated))
+}
+
+/// Reads the first gradient of an Ultra Fractal gradient file, whose colours are given as
+/// `index=... color=...` pairs, with the colour packed as `0xBBGGRR`
+fn parse_ugr(text: &str) -> Result<Vec<GradientStop>, String> {
+    let mut lines = text.lines().skip_while(|line| line.trim() != "gradient:");
+    if lines.next().is_none() {
+        return Err("missing gradient section".to_string());
+    }
+    // The gradient ends where the next section, like `opacity:`, or the next entry begins.
+    // Titles may contain colons too, so only a name without a value starts a section
+    let section = lines.take_while(|line| {
+        let first = line.split_whitespace().next().unwrap_or("");
+        !first.starts_with('}') && !(first.ends_with(':') && !first.contains('='))
+    });
+    let mut stops = vec![];
+    let mut index = None;
+    for token in section.flat_map(str::split_whitespace) {
+        if let Some(value) = token.strip_prefix("index=") {
+            index = Some(
+                value
+                    .parse::<i32>()
+                    .map_err(|_| format!("invalid index: {value}"))?,
+            );
+        } else if let Some(value) = token.strip_prefix("color=") {
+            let index = index.take().ok_or("colour without an index")?;
+            let packed: u32 = value
+                .parse()
+                .map_err(|_| format!("invalid colour: {value}"))?;
+            let rgb = uvec3(packed & 0xff, (packed >> 8) & 0xff, (packed >> 16) & 0xff);
+            stops.push(GradientStop {
+                colour: (rgb.as_vec3() / 255.0).to_array(),
+                position: index.rem_euclid(UGR_PERIOD as i32) as f32 / UGR_PERIOD,
+            });
+        }
+    }
+    if stops.is_empty() {
+        return Err("no colours".to_string());
+    }
+    Ok(stops)
+}
+
+fn to_rgb8(col: Vec3) -> UVec3 {
+    (col.clamp(Vec3::ZERO, Vec3::ONE) * 255.0)
+        .round()
+        .as_uvec3()
+}
+
+fn write_map(sample: impl Fn(f32) -> Vec3) -> String {
+    (0..MAP_SIZE)
+        .map(|k| {
+            let rgb = to_rgb8(sample(k as f32 / MAP_SIZE as f32));
+            format!("{} {} {}\n", rgb.x, rgb.y, rgb.z)
+        })
+        .collect()
+}
+
+/// Writes a linear rgb segment between each pair of neighbouring stops
+fn write_ggr(name: &str, stops: &[GradientStop]) -> String {
+    let num_segments = stops.len().saturating_sub(1);
+    let mut text = format!("GIMP Gradient\nName: {name}\n{num_segments}\n");
+    for (a, b) in stops.iter().zip(stops.iter().skip(1)) {
+        let mid = 0.5 * (a.position + b.position);
+        let (c0, c1) = (Vec3::from_array(a.colour), Vec3::from_array(b.colour));
+        text += &format!(
+            "{:.6} {mid:.6} {:.6} {:.6} {:.6} {:.6} 1.000000 {:.6} {:.6} {:.6} 1.000000 0 0\n",
+            a.position, b.position, c0.x, c0.y, c0.z, c1.x, c1.y, c1.z
+        );
+    }
+    text
+}
+
+fn write_ugr(name: &str, stops: &[GradientStop]) -> String {
+    let mut text = format!("{name} {{\ngradient:\n  title=\"{name}\" smooth=yes\n");
+    let mut last_index = None;
+    for stop in stops {
+        // The stop at 1 is the same as the one at 0, as the gradient repeats
+        let index = (stop.position * UGR_PERIOD).round() as i32;
+        if index >= UGR_PERIOD as i32 || last_index == Some(index) {
+            continue;
+        }
+        last_index = Some(index);
+        let rgb = to_rgb8(Vec3::from_array(stop.colour));
+        let packed = rgb.x | rgb.y << 8 | rgb.z << 16;
+        text += &format!("  index={index} color={packed}\n");
+    }
+    text += "}\n";
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_stops_eq(a: &[GradientStop], b: &[GradientStop], tolerance: f32) {
+        assert_eq!(a.len(), b.len(), "{a:?} != {b:?}");
+        for (a, b) in a.iter().zip(b) {
+            assert!(
+                (a.position - b.position).abs() <= tolerance,
+                "{a:?} != {b:?}"
+            );
+            let colour_error = Vec3::from_array(a.colour) - Vec3::from_array(b.colour);
+            assert!(
+                colour_error.abs().max_element() <= tolerance,
+                "{a:?} != {b:?}"
+            );
+        }
+    }
+
+    fn stop(position: f32, rgb: [u8; 3]) -> GradientStop {
+        GradientStop {
+            colour: rgb.map(|c| c as f32 / 255.0),
+            position,
+        }
+    }
+
+    #[test]
+    fn map_round_trip() {
+        let sample = |t: f32| vec3(t, 1.0 - t, 0.5);
+        let stops = parse_map(&write_map(sample)).unwrap();
+        let expected = (0..MAP_SIZE)
+            .map(|k| {
+                let position = k as f32 / MAP_SIZE as f32;
+                GradientStop {
+                    colour: sample(position).to_array(),
+                    position,
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_stops_eq(&stops, &expected, 0.51 / 255.0);
+    }
+
+    #[test]
+    fn ggr_round_trip() {
+        let stops = [
+            stop(0.0, [0, 8, 100]),
+            stop(0.3, [240, 255, 255]),
+            stop(0.7, [255, 170, 0]),
+            stop(1.0, [0, 8, 100]),
+        ];
+        let (parsed, approximated) = parse_ggr(&write_ggr("Test", &stops)).unwrap();
+        assert_stops_eq(&parsed, &stops, 1e-6);
+        assert_eq!(approximated, 0);
+    }
+
+    #[test]
+    fn ggr_counts_approximated_segments() {
+        let text = "GIMP Gradient
+Name: Test
+3
+0.0 0.25 0.5 0 0 0 1 1 1 1 1 0 0
+0.5 0.75 0.8 1 1 1 1 1 0 0 1 2 0
+0.8 0.9 1.0 1 0 0 1 0 0 1 1 0 1
+";
+        let (_, approximated) = parse_ggr(text).unwrap();
+        assert_eq!(approximated, 2);
+        assert!(write_ggr("Empty", &[]).ends_with("\n0\n"));
+    }
+
+    #[test]
+    fn ugr_round_trip() {
+        let stops = [
+            stop(0.0, [0, 8, 100]),
+            stop(0.25, [240, 255, 255]),
+            stop(0.5, [255, 170, 0]),
+            stop(1.0, [0, 8, 100]),
+        ];
+        let parsed = parse_ugr(&write_ugr("Test", &stops)).unwrap();
+        // The stop at 1 is left out, as the gradient repeats
+        assert_stops_eq(&parsed, &stops[..3], 1e-6);
+    }
+
+    #[test]
+    fn ugr_title_with_colon() {
+        let text = "test {
+gradient:
+  title=\"Test: colons\" smooth=yes
+  index=0 color=16711680
+  index=200 color=255
+opacity:
+  smooth=no index=0 opacity=255
+}
+";
+        let parsed = parse_ugr(text).unwrap();
+        assert_stops_eq(
+            &parsed,
+            &[stop(0.0, [0, 0, 255]), stop(0.5, [255, 0, 0])],
+            1e-6,
+        );
+    }
+}
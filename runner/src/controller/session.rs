@@ -0,0 +1,371 @@
+use super::{Controller, FieldLines, PRECISION, SlopeShading, Smooth, Stripe};
+use crate::big_vec2::BigVec2;
+use dashu::float::FBig;
+use dashu::integer::IBig;
+use easy_shader_runner::egui;
+use serde::{Deserialize, Serialize};
+use shared::formula::*;
+use shared::push_constants::shader::*;
+use shared::{MAX_LAYERS, MAX_ROOTS};
+use std::str::FromStr;
+
+#[cfg(not(target_arch = "wasm32"))]
+const SESSION_PATH: &str = "session.json";
+#[cfg(target_arch = "wasm32")]
+const SESSION_KEY: &str = "session";
+
+/// An arbitrary precision float, stored exactly as its significand and binary exponent
+#[derive(Serialize, Deserialize)]
+struct BigFloat {
+    significand: String,
+    exponent: isize,
+}
+
+impl From<&FBig> for BigFloat {
+    fn from(x: &FBig) -> Self {
+        Self {
+            significand: x.repr().significand().to_string(),
+            exponent: x.repr().exponent(),
+        }
+    }
+}
+
+impl BigFloat {
+    fn to_fbig(&self) -> Result<FBig, String> {
+        let significand = IBig::from_str(&self.significand).map_err(|err| err.to_string())?;
+        Ok(FBig::from_parts(significand, self.exponent))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BigPoint {
+    x: BigFloat,
+    y: BigFloat,
+}
+
+impl From<&BigVec2> for BigPoint {
+    fn from(v: &BigVec2) -> Self {
+        Self {
+            x: (&v.x).into(),
+            y: (&v.y).into(),
+        }
+    }
+}
+
+impl BigPoint {
+    fn to_big_vec2(&self) -> Result<BigVec2, String> {
+        Ok(BigVec2::new(self.x.to_fbig()?, self.y.to_fbig()?).with_precision(PRECISION))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CameraSession {
+    zoom: f64,
+    translate: BigPoint,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PolynomialSession {
+    roots: Vec<BigPoint>,
+    relaxation: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnimateSession {
+    enable: bool,
+    value: f32,
+    speed: f32,
+    reverse: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrapSession {
+    shape: TrapShape,
+    position: BigPoint,
+    size: f32,
+    angle: f32,
+}
+
+/// The colouring settings beyond the palettes. The picked trap image itself is not saved, only
+/// how it is drawn
+#[derive(Serialize, Deserialize)]
+struct ColouringSession {
+    dither: Dither,
+    smooth: Smooth,
+    animate: AnimateSession,
+    distance_estimate_thickness: f32,
+    slope_shading: SlopeShading,
+    stripe: Stripe,
+    field_lines: FieldLines,
+    orbit_trap: TrapSession,
+    image_trap_mode: ImageTrapMode,
+    image_trap_opacity: f32,
+    histogram_cycles: f32,
+}
+
+/// The view and colouring, saved so that they can be picked up again later. Left out are the
+/// trap image, the imported palette file, and what only affects how the app runs, such as debug
+/// mode, the fps counter and time slicing
+#[derive(Serialize, Deserialize)]
+struct Session {
+    mandelbrot: CameraSession,
+    julia: CameraSession,
+    marker: BigPoint,
+    render_julia_set: bool,
+    iterations: f64,
+    #[serde(default)]
+    formula: Formula,
+    #[serde(default)]
+    polynomial: Option<PolynomialSession>,
+    exponent: f64,
+    #[serde(default)]
+    exponent_im: f64,
+    #[serde(default)]
+    branch: Branch,
+    escape_radius: f32,
+    #[serde(default)]
+    outside: Colouring,
+    #[serde(default)]
+    inside: Colouring,
+    gradient: Vec<GradientStop>,
+    #[serde(default)]
+    cosine_palette: CosinePalette,
+    #[serde(default)]
+    gradient_interpolation: Interpolation,
+    #[serde(default)]
+    layers: Vec<Layer>,
+    /// Missing from older sessions, which then leave these settings as they are
+    #[serde(default)]
+    colouring: Option<ColouringSession>,
+    #[serde(default)]
+    render_partitioning: RenderPartitioning,
+}
+
+impl Controller {
+    pub fn session_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Save Session").clicked() {
+                self.session_status = Some(match self.save_session() {
+                    Ok(()) => "Session saved".to_string(),
+                    Err(err) => format!("Save failed: {err}"),
+                });
+            }
+            if ui.button("Load Session").clicked() {
+                self.session_status = Some(match self.load_session() {
+                    Ok(true) => "Session loaded".to_string(),
+                    Ok(false) => "No saved session".to_string(),
+                    Err(err) => format!("Load failed: {err}"),
+                });
+            }
+        });
+        if let Some(status) = &self.session_status {
+            ui.label(status);
+        }
+    }
+
+    fn current_session(&self) -> Session {
+        Session {
+            mandelbrot: CameraSession {
+                zoom: self.cameras.mandelbrot.zoom,
+                translate: (&self.cameras.mandelbrot.translate).into(),
+            },
+            julia: CameraSession {
+                zoom: self.cameras.julia.zoom,
+                translate: (&self.cameras.julia.translate).into(),
+            },
+            marker: (&self.marker_iterations.position).into(),
+            render_julia_set: self.render_julia_set,
+            iterations: self.num_iterations.n,
+            formula: self.formula,
+            polynomial: Some(PolynomialSession {
+                roots: self.polynomial.roots.iter().map(Into::into).collect(),
+                relaxation: self.polynomial.relaxation,
+            }),
+            exponent: self.exponent,
+            exponent_im: self.exponent_im,
+            branch: self.branch,
+            escape_radius: self.escape_radius,
+            outside: self.outside,
+            inside: self.inside,
+            gradient: self.gradient.stops.clone(),
+            cosine_palette: self.cosine_palette,
+            gradient_interpolation: self.gradient.interpolation,
+            layers: self.layers.clone(),
+            colouring: Some(ColouringSession {
+                dither: self.dither,
+                smooth: self.smooth,
+                animate: AnimateSession {
+                    enable: self.animate.enable,
+                    value: self.animate.value,
+                    speed: self.animate.speed,
+                    reverse: self.animate.reverse,
+                },
+                distance_estimate_thickness: self.distance_estimate_thickness,
+                slope_shading: self.slope_shading,
+                stripe: self.stripe,
+                field_lines: self.field_lines,
+                orbit_trap: TrapSession {
+                    shape: self.orbit_trap.shape,
+                    position: (&self.orbit_trap.position).into(),
+                    size: self.orbit_trap.size,
+                    angle: self.orbit_trap.angle,
+                },
+                image_trap_mode: self.trap_image.mode,
+                image_trap_opacity: self.trap_image.opacity,
+                histogram_cycles: self.histogram.cycles,
+            }),
+            render_partitioning: self.render_partitioning,
+        }
+    }
+
+    /// Everything is parsed before any of it is applied, so a session that fails to load leaves
+    /// the current state untouched
+    fn apply_session(&mut self, session: Session) -> Result<(), String> {
+        let mandelbrot_translate = session.mandelbrot.translate.to_big_vec2()?;
+        let julia_translate = session.julia.translate.to_big_vec2()?;
+        let marker = session.marker.to_big_vec2()?;
+        let mut polynomial = super::newton::Polynomial::default();
+        // Newton's method needs at least two roots to have anything to converge between
+        if let Some(saved) = session.polynomial
+            && saved.roots.len() >= 2
+        {
+            polynomial.roots = saved
+                .roots
+                .iter()
+                .take(MAX_ROOTS)
+                .map(BigPoint::to_big_vec2)
+                .collect::<Result<_, _>>()?;
+            polynomial.relaxation = saved.relaxation;
+        }
+        let trap_position = session
+            .colouring
+            .as_ref()
+            .map(|colouring| colouring.orbit_trap.position.to_big_vec2())
+            .transpose()?;
+
+        self.cameras.mandelbrot.translate = mandelbrot_translate;
+        self.cameras.mandelbrot.zoom = session.mandelbrot.zoom;
+        self.cameras.julia.translate = julia_translate;
+        self.cameras.julia.zoom = session.julia.zoom;
+        self.marker_iterations.position = marker;
+        self.render_julia_set = session.render_julia_set;
+        self.num_iterations.n = session.iterations;
+        self.formula = session.formula;
+        self.polynomial = polynomial;
+        self.exponent = session.exponent;
+        self.exponent_im = session.exponent_im;
+        self.branch = session.branch;
+        self.escape_radius = session.escape_radius;
+        self.outside = session.outside;
+        self.inside = session.inside;
+        self.gradient.set_stops(session.gradient);
+        self.cosine_palette = session.cosine_palette;
+        self.gradient.interpolation = session.gradient_interpolation;
+        self.layers = session.layers;
+        self.layers.truncate(MAX_LAYERS);
+        if let Some(colouring) = session.colouring
+            && let Some(trap_position) = trap_position
+        {
+            self.dither = colouring.dither;
+            self.smooth = colouring.smooth;
+            self.animate.enable = colouring.animate.enable;
+            self.animate.value = colouring.animate.value;
+            self.animate.speed = colouring.animate.speed;
+            self.animate.reverse = colouring.animate.reverse;
+            self.distance_estimate_thickness = colouring.distance_estimate_thickness;
+            self.slope_shading = colouring.slope_shading;
+            self.stripe = colouring.stripe;
+            self.field_lines = colouring.field_lines;
+            self.orbit_trap.shape = colouring.orbit_trap.shape;
+            self.orbit_trap.position = trap_position;
+            self.orbit_trap.size = colouring.orbit_trap.size;
+            self.orbit_trap.angle = colouring.orbit_trap.angle;
+            self.trap_image.mode = colouring.image_trap_mode;
+            self.trap_image.opacity = colouring.image_trap_opacity;
+            self.histogram.cycles = colouring.histogram_cycles;
+        }
+        self.render_partitioning = session.render_partitioning;
+        self.histogram.stale = true;
+
+        self.cameras.mandelbrot.needs_reiterate = true;
+        self.cameras.julia.needs_reiterate = true;
+        self.mandelbrot_reference.recompute = true;
+        self.marker_iterations.recompute = self.marker_iterations.enabled;
+        Ok(())
+    }
+
+    pub fn save_session(&self) -> Result<(), String> {
+        let json =
+            serde_json::to_string_pretty(&self.current_session()).map_err(|err| err.to_string())?;
+        #[cfg(not(target_arch = "wasm32"))]
+        std::fs::write(SESSION_PATH, json).map_err(|err| err.to_string())?;
+        #[cfg(target_arch = "wasm32")]
+        local_storage()?
+            .set_item(SESSION_KEY, &json)
+            .map_err(|err| format!("{err:?}"))?;
+        Ok(())
+    }
+
+    /// Returns whether there was a saved session to load
+    pub fn load_session(&mut self) -> Result<bool, String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let json = match std::fs::read_to_string(SESSION_PATH) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.to_string()),
+        };
+        #[cfg(target_arch = "wasm32")]
+        let Some(json) = local_storage()?
+            .get_item(SESSION_KEY)
+            .map_err(|err| format!("{err:?}"))?
+        else {
+            return Ok(false);
+        };
+        let session = serde_json::from_str(&json).map_err(|err| err.to_string())?;
+        self.apply_session(session)?;
+        Ok(true)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, String> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| "local storage is unavailable".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_round_trips_the_colouring() {
+        let mut saved = Controller::new(&crate::Options { debug: false });
+        saved.outside.style = RenderStyle::StripeAverage;
+        saved.dither = Dither::Noise;
+        saved.smooth.enable = false;
+        saved.animate.speed = 0.25;
+        saved.distance_estimate_thickness = 3.5;
+        saved.slope_shading.enable = true;
+        saved.slope_shading.light.angle = 1.0;
+        saved.stripe.density = 7.0;
+        saved.field_lines.width = 0.3;
+        saved.orbit_trap.shape = TrapShape::Ring;
+        saved.orbit_trap.position = BigVec2::from_f64s(0.25, -0.5).with_precision(PRECISION);
+        saved.trap_image.mode = ImageTrapMode::Blend;
+        saved.histogram.cycles = 5.0;
+        saved.render_partitioning = RenderPartitioning::Inside;
+        let json = serde_json::to_string(&saved.current_session()).unwrap();
+
+        let mut loaded = Controller::new(&crate::Options { debug: false });
+        loaded
+            .apply_session(serde_json::from_str(&json).unwrap())
+            .unwrap();
+        assert_eq!(loaded.orbit_trap.shape, TrapShape::Ring);
+        assert_eq!(loaded.stripe.density, 7.0);
+        assert_eq!(
+            serde_json::to_string(&loaded.current_session()).unwrap(),
+            json
+        );
+    }
+}
@@ -8,6 +8,8 @@ pub enum TouchType {
     Julia,
     RenderSplit,
     Marker,
+    OrbitTrap,
+    Root(usize),
 }
 
 #[derive(Clone, Copy)]
@@ -61,6 +63,13 @@ impl Controller {
                     self.marker_iterations.recompute = self.marker_iterations.enabled;
                     self.cameras.julia.needs_reiterate = true;
                 }
+                TouchType::OrbitTrap => {
+                    self.orbit_trap.position +=
+                        self.to_uv_space_big(position) - self.to_uv_space_big(last_position);
+                    self.cameras.mandelbrot.needs_reiterate = true;
+                    self.cameras.julia.needs_reiterate = true;
+                }
+                TouchType::Root(k) => self.drag_root(k, last_position, position),
             }
             self.touches.get_mut(&id).unwrap().pos = position;
         }
@@ -113,8 +122,12 @@ impl Controller {
                 }
                 let touch_type = if self.pos_on_render_split(position).is_some() {
                     TouchType::RenderSplit
+                } else if let Some(k) = self.pos_on_root(position) {
+                    TouchType::Root(k)
                 } else if self.pos_on_marker(position) {
                     TouchType::Marker
+                } else if self.pos_on_orbit_trap(position) {
+                    TouchType::OrbitTrap
                 } else if self.is_pos_in_julia(position) {
                     TouchType::Julia
                 } else {
@@ -0,0 +1,204 @@
+use super::Controller;
+use easy_shader_runner::{GraphicsContext, egui, wgpu};
+use glam::*;
+use shared::push_constants::shader::*;
+#[cfg(target_arch = "wasm32")]
+use std::sync::{Arc, Mutex};
+
+/// Side length of the square texture that loaded images are resampled to
+pub const TRAP_IMAGE_SIZE: u32 = 512;
+
+pub struct TrapImage {
+    pub mode: ImageTrapMode,
+    pub opacity: f32,
+    pub aspect: f32,
+    pub texture: Option<wgpu::Texture>,
+    /// Resampled rgba pixels waiting to be written to the texture
+    pixels: Option<Vec<u8>>,
+    /// Contents of the file picked by the user, filled in once the browser has read it
+    #[cfg(target_arch = "wasm32")]
+    picked_file: Arc<Mutex<Option<Vec<u8>>>>,
+    status: Option<String>,
+}
+
+impl Default for TrapImage {
+    fn default() -> Self {
+        Self {
+            mode: ImageTrapMode::default(),
+            opacity: 0.5,
+            aspect: 1.0,
+            texture: None,
+            pixels: Some(checkerboard()),
+            #[cfg(target_arch = "wasm32")]
+            picked_file: Default::default(),
+            status: None,
+        }
+    }
+}
+
+impl TrapImage {
+    fn load(&mut self, bytes: &[u8]) {
+        match decode_png(bytes) {
+            Ok((size, rgba)) => {
+                self.aspect = size.x as f32 / size.y as f32;
+                self.pixels = Some(resample(size, &rgba));
+                self.status = Some(format!("Loaded {} x {}", size.x, size.y));
+            }
+            Err(err) => self.status = Some(format!("Failed to load image: {err}")),
+        }
+    }
+
+    fn pick_file(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .pick_file()
+        {
+            match std::fs::read(&path) {
+                Ok(bytes) => self.load(&bytes),
+                Err(err) => {
+                    self.status = Some(format!("Failed to read {}: {err}", path.display()));
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let picked_file = self.picked_file.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(file) = rfd::AsyncFileDialog::new()
+                    .add_filter("PNG", &["png"])
+                    .pick_file()
+                    .await
+                {
+                    *picked_file.lock().unwrap() = Some(file.read().await);
+                }
+            });
+        }
+    }
+}
+
+impl Controller {
+    pub fn trap_image_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let trap_image = &mut self.trap_image;
+        let mut changed = false;
+        if ui.button("Load PNG").clicked() {
+            trap_image.pick_file();
+        }
+        if let Some(status) = &trap_image.status {
+            ui.label(status);
+        }
+        ui.horizontal(|ui| {
+            changed |= ui
+                .radio_value(&mut trap_image.mode, ImageTrapMode::FirstHit, "First Hit")
+                .changed();
+            changed |= ui
+                .radio_value(&mut trap_image.mode, ImageTrapMode::Blend, "Blend")
+                .changed();
+        });
+        if trap_image.mode == ImageTrapMode::Blend {
+            ui.label("Opacity");
+            changed |= ui
+                .add(egui::Slider::new(&mut trap_image.opacity, 0.01..=1.0))
+                .changed();
+        }
+        changed
+    }
+
+    /// Writes a newly loaded image to the texture sampled by the shader
+    pub fn update_trap_image(&mut self, graphics_context: &GraphicsContext) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let picked_file = self.trap_image.picked_file.lock().unwrap().take();
+            if let Some(bytes) = picked_file {
+                self.trap_image.load(&bytes);
+            }
+        }
+        let Some(texture) = &self.trap_image.texture else {
+            return;
+        };
+        let Some(pixels) = self.trap_image.pixels.take() else {
+            return;
+        };
+        let size = wgpu::Extent3d {
+            width: TRAP_IMAGE_SIZE,
+            height: TRAP_IMAGE_SIZE,
+            depth_or_array_layers: 1,
+        };
+        graphics_context.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * TRAP_IMAGE_SIZE),
+                rows_per_image: Some(TRAP_IMAGE_SIZE),
+            },
+            size,
+        );
+        self.cameras.mandelbrot.needs_reiterate = true;
+        self.cameras.julia.needs_reiterate = true;
+    }
+}
+
+/// Decodes a png into its size and 8 bit rgba pixels
+fn decode_png(bytes: &[u8]) -> Result<(UVec2, Vec<u8>), png::DecodingError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    let pixels = &buffer[..info.buffer_size()];
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        // Palettes are expanded by `normalize_to_color8`
+        png::ColorType::Indexed => unreachable!(),
+    };
+    Ok((uvec2(info.width, info.height), rgba))
+}
+
+fn resample(size: UVec2, rgba: &[u8]) -> Vec<u8> {
+    let scale = size.as_vec2() / TRAP_IMAGE_SIZE as f32;
+    let mut pixels = Vec::with_capacity((TRAP_IMAGE_SIZE * TRAP_IMAGE_SIZE * 4) as usize);
+    for y in 0..TRAP_IMAGE_SIZE {
+        for x in 0..TRAP_IMAGE_SIZE {
+            let p = ((uvec2(x, y).as_vec2() + 0.5) * scale)
+                .as_uvec2()
+                .min(size - 1);
+            let i = ((p.y * size.x + p.x) * 4) as usize;
+            pixels.extend_from_slice(&rgba[i..i + 4]);
+        }
+    }
+    pixels
+}
+
+/// Shown until an image is loaded, with transparent squares to make blending visible
+fn checkerboard() -> Vec<u8> {
+    let mut pixels = Vec::with_capacity((TRAP_IMAGE_SIZE * TRAP_IMAGE_SIZE * 4) as usize);
+    for y in 0..TRAP_IMAGE_SIZE {
+        for x in 0..TRAP_IMAGE_SIZE {
+            let uv = uvec2(x, y).as_vec2() / TRAP_IMAGE_SIZE as f32;
+            let square = (uv * 8.0).as_uvec2();
+            let alpha = if (square.x + square.y) % 2 == 0 {
+                255
+            } else {
+                0
+            };
+            let col = (uv.extend(1.0 - uv.x) * 255.0).as_uvec3();
+            pixels.extend([col.x as u8, col.y as u8, col.z as u8, alpha]);
+        }
+    }
+    pixels
+}
@@ -1,7 +1,9 @@
-use super::Controller;
+use super::{Controller, PRECISION};
+use crate::big_vec2::BigVec2;
 use easy_shader_runner::{UiState, egui};
 use glam::*;
 use push_constants::shader::*;
+use shared::formula::*;
 use shared::*;
 use web_time::Instant;
 
@@ -19,7 +21,7 @@ impl Controller {
         }
         self.handle_param_deltas();
         self.iteration_mode = if self.cameras.mandelbrot.zoom > 1000.0 {
-            if self.exponent == 2.0 {
+            if self.formula.is_perturbable(self.complex_exponent()) {
                 IterationMode::Perturbation
             } else {
                 let dialog_width = 160.0;
@@ -29,7 +31,9 @@ impl Controller {
                     .max_width(dialog_width)
                     .fixed_pos(egui::pos2(self.size.x as f32 - dialog_width - 15.0, 10.0))
                     .show(ctx, |ui| {
-                        ui.label("Deep zoom is only supported on exponent of 2");
+                        ui.label(
+                            "Deep zoom is only supported on exponent of 2, and not for Newton",
+                        );
                     });
                 IterationMode::Regular
             }
@@ -50,6 +54,14 @@ impl Controller {
         if self.marker_iterations.recompute {
             self.recompute_iterations(graphics_context);
         }
+        self.update_trap_image(graphics_context);
+        self.update_histogram(graphics_context);
+        #[cfg(target_arch = "wasm32")]
+        self.update_palette_file();
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.palette_cycle_export.requested {
+            self.export_palette_cycle(graphics_context);
+        }
         if self.show_fps {
             self.fps_window(ctx, ui_state);
         }
@@ -79,10 +91,11 @@ impl Controller {
         let mut z = Complex::ZERO.with_precision(128);
         let mut i = 0;
         let num_iters = self.calculate_num_iterations() as u32;
+        let formula = self.formula;
         while i < num_iters && z.norm_squared() < escape_radius_squared {
             self.mandelbrot_reference.points.push(z.as_vec2());
             i += 1;
-            z = z.square() + c.clone();
+            z = formula.step_quadratic(z, c.clone());
         }
         self.mandelbrot_reference.points.push(z.as_vec2());
         self.mandelbrot_reference.num_ref_iterations = i;
@@ -102,14 +115,22 @@ impl Controller {
         use shared::complex::Complex;
         debug_assert!(self.marker_iterations.enabled);
         let c = Complex::from(self.marker_iterations.position.as_vec2());
-        let mut z = Complex::ZERO;
+        let formula = self.formula;
+        let constants = self.fragment_constants();
+        let newton = self.polynomial.newton();
+        // Root finding starts from the marker instead
+        let mut z = if formula.is_root_finding() {
+            c
+        } else {
+            Complex::ZERO
+        };
         let mut stats = super::MarkerIterationStats::default();
         let mut prev_z = Complex::new(-1.0, 0.0);
         let mut prev_prev_z;
-        let mut prev_norm;
         let mut norm = 0.0;
         let mut i = 0;
         let num_iters = self.calculate_num_iterations().ceil() as u32;
+        let mut arg = 0.0;
         self.marker_iterations.points.push(z.into());
         loop {
             if i >= num_iters {
@@ -117,34 +138,26 @@ impl Controller {
             }
             prev_prev_z = prev_z;
             prev_z = z;
-            if self.exponent == 2.0 {
-                z = z * z + c;
-            } else {
-                z = z.powf(self.exponent as f32).to_rectangular() + c;
-            }
+            z = formula.step(&constants, &newton, z, c, &mut arg);
             i += 1;
             stats.angle_sum +=
                 angle_between_three_points(prev_prev_z.into(), prev_z.into(), z.into());
             stats.distance_sum += prev_z.distance(z);
-            prev_norm = norm;
             norm = z.abs();
             stats.norm_sum += norm;
             self.marker_iterations.points.push(z.into());
-            if norm >= self.escape_radius {
+            if formula.has_bailed_out(&constants, prev_z.into(), z.into()) {
                 stats.final_distance = prev_z.distance(z);
                 stats.final_angle = z.arg();
                 stats.count = i;
                 stats.final_norm = norm;
-                stats.proximity = get_proximity(prev_norm, norm, self.escape_radius);
-                while norm < self.escape_radius.max(1e4) {
+                stats.proximity = formula.proximity(&constants, prev_z.into(), z.into());
+                // Escaping orbits are followed a little further to show where they head
+                while !formula.is_root_finding() && norm < self.escape_radius.max(1e4) {
                     if i >= num_iters {
                         break;
                     }
-                    if self.exponent == 2.0 {
-                        z = z * z + c;
-                    } else {
-                        z = z.powf(self.exponent as f32).to_rectangular() + c;
-                    }
+                    z = formula.step(&constants, &newton, z, c, &mut arg);
                     norm = z.abs();
                     self.marker_iterations.points.push(z.into());
                     i += 1;
@@ -156,7 +169,7 @@ impl Controller {
                 stats.final_angle = z.arg();
                 stats.count = i;
                 stats.final_norm = norm;
-                stats.proximity = get_proximity(prev_norm, norm, self.escape_radius);
+                stats.proximity = formula.proximity(&constants, prev_z.into(), z.into());
             }
         }
         self.marker_iterations.stats = stats;
@@ -194,7 +207,9 @@ impl Controller {
             }
         }
         if self.delta_params.period != 0.0 {
-            self.palette_period *= (self.delta_params.period as f32 - 1.0) * dt as f32 + 1.0;
+            let scale = (self.delta_params.period as f32 - 1.0) * dt as f32 + 1.0;
+            self.outside.period *= scale;
+            self.inside.period *= scale;
         }
         if self.delta_params.animation_speed != 0.0 {
             self.animate.speed *=
@@ -217,11 +232,17 @@ impl Controller {
     }
 
     fn handle_cursor_icon(&mut self, ctx: &egui::Context) {
-        if self.marker_iterations.dragging {
+        if self.marker_iterations.dragging
+            || self.orbit_trap.dragging
+            || self.polynomial.dragging.is_some()
+        {
             ctx.set_cursor_icon(egui::CursorIcon::Grabbing);
         } else if let Some(icon) = self.render_split.dragging {
             ctx.set_cursor_icon(icon);
-        } else if self.can_grab_marker() {
+        } else if self.can_grab_marker()
+            || self.can_grab_orbit_trap()
+            || self.can_grab_root().is_some()
+        {
             ctx.set_cursor_icon(egui::CursorIcon::Grab);
         } else if let Some(icon) = self.can_grab_render_split() {
             ctx.set_cursor_icon(icon);
@@ -258,7 +279,6 @@ impl Controller {
             ui.label(egui::RichText::new("Render Partitioning").size(15.0));
         });
         egui::Grid::new("render_partitioning_grid").show(ui, |ui| {
-            let render_partioning = self.render_partitioning;
             ui.radio_value(
                 &mut self.render_partitioning,
                 RenderPartitioning::Outside,
@@ -275,13 +295,137 @@ impl Controller {
                 "Both",
             );
             ui.end_row();
-            if self.render_partitioning != render_partioning {
-                self.cameras.mandelbrot.needs_reiterate = true;
-                self.cameras.julia.needs_reiterate = true;
-            }
         });
     }
 
+    fn orbit_trap_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        if self.uses_style(RenderStyle::ImageTrap) {
+            changed |= self.trap_image_ui(ui);
+        }
+        let trap = &mut self.orbit_trap;
+        if self.uses_style(RenderStyle::OrbitTrap) {
+            let shape_before = trap.shape;
+            egui::ComboBox::from_label("Trap")
+                .selected_text(format!("{:?}", trap.shape))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut trap.shape, TrapShape::Point, "Point");
+                    ui.selectable_value(&mut trap.shape, TrapShape::Line, "Line");
+                    ui.selectable_value(&mut trap.shape, TrapShape::Cross, "Cross");
+                    ui.selectable_value(&mut trap.shape, TrapShape::Circle, "Circle");
+                    ui.selectable_value(&mut trap.shape, TrapShape::Ring, "Ring");
+                });
+            changed |= trap.shape != shape_before;
+        }
+        egui::Grid::new("orbit_trap_grid").show(ui, |ui| {
+            let mut position = trap.position.as_dvec2();
+            ui.label("Position");
+            ui.horizontal(|ui| {
+                let x_changed = ui
+                    .add(egui::DragValue::new(&mut position.x).speed(0.01))
+                    .changed();
+                let y_changed = ui
+                    .add(egui::DragValue::new(&mut position.y).speed(0.01))
+                    .changed();
+                if x_changed || y_changed {
+                    trap.position = BigVec2::from_dvec2(position).with_precision(PRECISION);
+                    changed = true;
+                }
+            });
+            ui.end_row();
+            ui.label("Size");
+            changed |= ui
+                .add(egui::Slider::new(&mut trap.size, 0.01..=4.0).logarithmic(true))
+                .changed();
+            ui.end_row();
+            ui.label("Angle");
+            changed |= ui.drag_angle(&mut trap.angle).changed();
+            ui.end_row();
+        });
+        if changed {
+            self.cameras.mandelbrot.needs_reiterate = true;
+            self.cameras.julia.needs_reiterate = true;
+        }
+    }
+
+    /// Palette, style and how values map onto the palette, side by side for the outside and
+    /// inside
+    fn colouring_ui(&mut self, ui: &mut egui::Ui) {
+        let before = (self.outside, self.inside);
+        egui::Grid::new("colouring_grid").show(ui, |ui| {
+            ui.label("");
+            ui.label(egui::RichText::new("Outside").size(15.0));
+            ui.label(egui::RichText::new("Inside").size(15.0));
+            ui.end_row();
+
+            ui.label("Palette");
+            palette_combo(ui, "outside_palette", &mut self.outside.palette);
+            palette_combo(ui, "inside_palette", &mut self.inside.palette);
+            ui.end_row();
+
+            ui.label("Style");
+            style_combo(ui, "outside_style", &mut self.outside.style);
+            style_combo(ui, "inside_style", &mut self.inside.style);
+            ui.end_row();
+
+            ui.label("Period");
+            ui.add_enabled(
+                self.outside.transfer != Transfer::Rank,
+                egui::DragValue::new(&mut self.outside.period)
+                    .range(0.01..=1.0)
+                    .speed(0.002),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.inside.period)
+                    .range(0.01..=1.0)
+                    .speed(0.002),
+            );
+            ui.end_row();
+
+            ui.label("Offset");
+            ui.add(egui::DragValue::new(&mut self.outside.offset).speed(0.005));
+            ui.add(egui::DragValue::new(&mut self.inside.offset).speed(0.005));
+            ui.end_row();
+
+            ui.label("Transfer");
+            transfer_ui(ui, "outside_transfer", &mut self.outside, true);
+            transfer_ui(ui, "inside_transfer", &mut self.inside, false);
+            ui.end_row();
+
+            ui.label("Wrap");
+            wrap_combo(ui, "outside_wrap", &mut self.outside.wrap);
+            wrap_combo(ui, "inside_wrap", &mut self.inside.wrap);
+            ui.end_row();
+        });
+        if self.inside.style != before.1.style
+            && self.render_partitioning == RenderPartitioning::Outside
+        {
+            // A new inside style has nothing to show with the inside hidden
+            self.render_partitioning = RenderPartitioning::Both;
+        }
+        if self.outside.style != before.0.style {
+            self.histogram.stale = true;
+        }
+    }
+
+    fn slope_shading_ui(&mut self, ui: &mut egui::Ui) {
+        ui.toggle_value(&mut self.slope_shading.enable, "Slope Shading");
+        if self.slope_shading.enable {
+            let light = &mut self.slope_shading.light;
+            egui::Grid::new("slope_shading_grid").show(ui, |ui| {
+                ui.label("Angle");
+                ui.drag_angle(&mut light.angle);
+                ui.end_row();
+                ui.label("Elevation");
+                ui.add(egui::Slider::new(&mut light.elevation, 0.0..=4.0));
+                ui.end_row();
+                ui.label("Intensity");
+                ui.add(egui::Slider::new(&mut light.intensity, 0.0..=1.0));
+                ui.end_row();
+            });
+        }
+    }
+
     fn main_window(
         &mut self,
         ctx: &egui::Context,
@@ -293,79 +437,79 @@ impl Controller {
             .resizable(false)
             .show(ctx, |ui| {
                 ui.style_mut().spacing.slider_width = width;
-                egui::ComboBox::from_label(egui::RichText::new("Palette").size(15.0))
-                    .selected_text(format!("{:?}", self.palette))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(&mut self.palette, Palette::Pastel, "Pastel");
-                        ui.selectable_value(
-                            &mut self.palette,
-                            Palette::SolarizedDark,
-                            "SolarizedDark",
-                        );
-                        ui.selectable_value(&mut self.palette, Palette::Copper, "Copper");
-                        ui.selectable_value(&mut self.palette, Palette::RedAndBlack, "RedAndBlack");
-                        ui.selectable_value(&mut self.palette, Palette::NeonA, "NeonA");
-                        ui.selectable_value(&mut self.palette, Palette::Highlighter, "Highlighter");
-                        ui.selectable_value(&mut self.palette, Palette::NeonB, "NeonB");
-                        ui.selectable_value(&mut self.palette, Palette::RGB, "RGB");
-                        ui.selectable_value(&mut self.palette, Palette::NeonC, "NeonC");
-                        ui.selectable_value(&mut self.palette, Palette::Zebra, "Zebra");
-                    });
+                self.colouring_ui(ui);
+                self.layers_ui(ui);
+                if self.uses_palette(Palette::Custom) {
+                    self.gradient_ui(ui);
+                }
+                if self.uses_palette(Palette::Cosine) {
+                    self.cosine_palette_ui(ui);
+                }
+                self.palette_file_ui(ui);
+                ui.horizontal(|ui| {
+                    ui.label("Dither");
+                    ui.radio_value(&mut self.dither, Dither::None, "None");
+                    ui.radio_value(&mut self.dither, Dither::Ordered, "Ordered");
+                    ui.radio_value(&mut self.dither, Dither::Noise, "Noise");
+                });
+                if self.outside.transfer == Transfer::Rank {
+                    self.histogram_ui(ui);
+                }
                 ui.separator();
 
-                let render_style_before = self.render_style;
-                egui::ComboBox::from_label(egui::RichText::new("Style").size(15.0))
-                    .selected_text(format!("{:?}", render_style_before))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut self.render_style,
-                            RenderStyle::Iterations,
-                            "Iterations",
-                        );
-                        ui.selectable_value(
-                            &mut self.render_style,
-                            RenderStyle::FinalDistance,
-                            "Final Distance",
-                        );
-                        ui.selectable_value(
-                            &mut self.render_style,
-                            RenderStyle::FinalAngle,
-                            "Final Angle",
-                        );
-                        ui.selectable_value(
-                            &mut self.render_style,
-                            RenderStyle::DistanceSum,
-                            "Distance Sum",
-                        );
-                        ui.selectable_value(
-                            &mut self.render_style,
-                            RenderStyle::NormSum,
-                            "Norm Sum",
-                        );
-                        ui.selectable_value(
-                            &mut self.render_style,
-                            RenderStyle::FinalNorm,
-                            "Final Norm",
-                        );
-                        ui.selectable_value(
-                            &mut self.render_style,
-                            RenderStyle::AngleSum,
-                            "Angle Sum",
+                if self.uses_style(RenderStyle::DistanceEstimate) {
+                    ui.label("Thickness");
+                    ui.add(
+                        egui::Slider::new(&mut self.distance_estimate_thickness, 0.1..=20.0)
+                            .logarithmic(true),
+                    );
+                }
+                if self.uses_style(RenderStyle::OrbitTrap)
+                    || self.uses_style(RenderStyle::ImageTrap)
+                {
+                    self.orbit_trap_ui(ui);
+                }
+                if self.uses_style(RenderStyle::BinaryDecomposition) {
+                    ui.horizontal(|ui| {
+                        ui.toggle_value(&mut self.field_lines.enable, "Field Lines")
+                            .on_hover_text(
+                                "Rays along the angle of the final iteration, which double at \
+                                 every band rather than following the true external angle",
+                            );
+                        ui.add_enabled(
+                            self.field_lines.enable,
+                            egui::DragValue::new(&mut self.field_lines.width)
+                                .range(0.01..=0.5)
+                                .speed(0.005),
                         );
                     });
-                if self.render_style != render_style_before {
-                    self.cameras.mandelbrot.needs_reiterate = true;
-                    self.cameras.julia.needs_reiterate = true;
+                }
+                if self.uses_style(RenderStyle::StripeAverage) {
+                    let mut changed = false;
+                    egui::Grid::new("stripe_grid").show(ui, |ui| {
+                        ui.label("Density");
+                        // Whole densities keep the stripes continuous where the argument wraps
+                        changed |= ui
+                            .add(egui::Slider::new(&mut self.stripe.density, 1.0..=20.0).integer())
+                            .changed();
+                        ui.end_row();
+                        ui.label("Skip");
+                        changed |= ui
+                            .add(egui::Slider::new(&mut self.stripe.skip, 0..=10))
+                            .changed();
+                        ui.end_row();
+                    });
+                    if changed {
+                        self.cameras.mandelbrot.needs_reiterate = true;
+                        self.cameras.julia.needs_reiterate = true;
+                    }
                 }
                 ui.separator();
 
                 self.render_partition_ui(ui);
                 ui.separator();
 
-                ui.vertical_centered(|ui| {
-                    ui.label(egui::RichText::new("Period").size(14.0));
-                });
-                ui.add(egui::Slider::new(&mut self.palette_period, 0.01..=1.0));
+                self.slope_shading_ui(ui);
                 ui.separator();
 
                 ui.vertical_centered(|ui| {
@@ -384,16 +528,64 @@ impl Controller {
                 }
                 ui.separator();
                 ui.vertical_centered(|ui| {
-                    ui.label(egui::RichText::new("Exponent").size(14.0));
+                    ui.label(egui::RichText::new("Formula").size(14.0));
                 });
-                if ui
-                    .add(egui::Slider::new(&mut self.exponent, -10.0..=10.0))
-                    .changed()
-                {
+                let formula = self.formula;
+                egui::ComboBox::from_id_salt("formula")
+                    .selected_text(
+                        FORMULAS
+                            .iter()
+                            .find(|(value, _)| *value == self.formula)
+                            .map_or("", |(_, label)| label),
+                    )
+                    .show_ui(ui, |ui| {
+                        for (value, label) in FORMULAS {
+                            ui.selectable_value(&mut self.formula, value, label);
+                        }
+                    });
+                if self.formula != formula {
                     self.marker_iterations.recompute = self.marker_iterations.enabled;
                     self.mandelbrot_reference.recompute = true;
                     self.cameras.mandelbrot.needs_reiterate = true;
                     self.cameras.julia.needs_reiterate = true;
+                    // Orbits that converge count as outside, and are told apart by their root
+                    if self.formula.is_root_finding() {
+                        self.outside.style = RenderStyle::Root;
+                    } else if self.outside.style == RenderStyle::Root {
+                        self.outside.style = RenderStyle::default();
+                    }
+                    self.histogram.stale = true;
+                }
+                ui.separator();
+                // The exponent has no part in root finding
+                if self.formula.is_root_finding() {
+                    self.newton_ui(ui);
+                } else {
+                    ui.vertical_centered(|ui| {
+                        ui.label(egui::RichText::new("Exponent").size(14.0));
+                    });
+                    let mut exponent_changed = ui
+                        .add(egui::Slider::new(&mut self.exponent, -10.0..=10.0).text("re"))
+                        .changed();
+                    exponent_changed |= ui
+                        .add(egui::Slider::new(&mut self.exponent_im, -4.0..=4.0).text("im"))
+                        .changed();
+                    if !self.complex_exponent().is_integer() {
+                        let branch = self.branch;
+                        ui.horizontal(|ui| {
+                            ui.label("Branch");
+                            ui.radio_value(&mut self.branch, Branch::Principal, "Principal");
+                            ui.radio_value(&mut self.branch, Branch::Positive, "Positive");
+                            ui.radio_value(&mut self.branch, Branch::Continuous, "Continuous");
+                        });
+                        exponent_changed |= self.branch != branch;
+                    }
+                    if exponent_changed {
+                        self.marker_iterations.recompute = self.marker_iterations.enabled;
+                        self.mandelbrot_reference.recompute = true;
+                        self.cameras.mandelbrot.needs_reiterate = true;
+                        self.cameras.julia.needs_reiterate = true;
+                    }
                 }
                 ui.separator();
                 use super::NumIterationsMode;
@@ -427,6 +619,15 @@ impl Controller {
                     self.cameras.mandelbrot.needs_reiterate = true;
                     self.cameras.julia.needs_reiterate = true;
                 };
+                ui.horizontal(|ui| {
+                    ui.toggle_value(&mut self.time_slice.enable, "Time Slice");
+                    ui.add_enabled(
+                        self.time_slice.enable,
+                        egui::DragValue::new(&mut self.time_slice.budget_ms)
+                            .range(5.0..=200.0)
+                            .suffix(" ms"),
+                    );
+                });
                 ui.separator();
                 let smooth_toggled = ui.toggle_value(&mut self.smooth.enable, "Smooth").changed();
                 if ui
@@ -437,8 +638,7 @@ impl Controller {
                     .changed()
                     || smooth_toggled
                 {
-                    self.cameras.mandelbrot.needs_reiterate = true;
-                    self.cameras.julia.needs_reiterate = true;
+                    self.histogram.stale = true;
                 }
                 ui.separator();
                 ui.horizontal(|ui| {
@@ -476,6 +676,13 @@ impl Controller {
                     }
                 }
                 ui.separator();
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    self.export_ui(ui);
+                    ui.separator();
+                }
+                self.session_ui(ui);
+                ui.separator();
                 ui.horizontal(|ui| {
                     ui.checkbox(&mut self.debug, "Debug");
                     ui.checkbox(&mut self.show_fps, "FPS");
@@ -584,6 +791,12 @@ impl Controller {
                     ui.monospace(format!("{:?}", self.iteration_mode));
                     ui.end_row();
 
+                    if self.time_slice.is_enabled(self.size) {
+                        ui.label("iterations per frame");
+                        ui.monospace(format!("{:.0}", self.time_slice.iterations_per_frame));
+                        ui.end_row();
+                    }
+
                     if self.marker_iterations.enabled {
                         ui.label("num iterations");
                         ui.monospace(format!("{:.2}", self.marker_iterations.stats.count));
@@ -648,3 +861,113 @@ fn angle_between_three_points(a: Vec2, b: Vec2, c: Vec2) -> f32 {
     let bc = c - b;
     (_cross(ab, bc)).atan2(ab.dot(bc))
 }
+
+pub(super) fn palette_combo(ui: &mut egui::Ui, id_salt: &str, palette: &mut Palette) {
+    egui::ComboBox::from_id_salt(id_salt)
+        .selected_text(format!("{palette:?}"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(palette, Palette::Pastel, "Pastel");
+            ui.selectable_value(palette, Palette::SolarizedDark, "SolarizedDark");
+            ui.selectable_value(palette, Palette::Copper, "Copper");
+            ui.selectable_value(palette, Palette::RedAndBlack, "RedAndBlack");
+            ui.selectable_value(palette, Palette::NeonA, "NeonA");
+            ui.selectable_value(palette, Palette::Highlighter, "Highlighter");
+            ui.selectable_value(palette, Palette::NeonB, "NeonB");
+            ui.selectable_value(palette, Palette::RGB, "RGB");
+            ui.selectable_value(palette, Palette::NeonC, "NeonC");
+            ui.selectable_value(palette, Palette::Zebra, "Zebra");
+            ui.selectable_value(palette, Palette::Custom, "Custom");
+            ui.selectable_value(palette, Palette::Cosine, "Cosine");
+        });
+}
+
+const STYLES: [(RenderStyle, &str); 18] = [
+    (RenderStyle::Iterations, "Iterations"),
+    (RenderStyle::FinalDistance, "Final Distance"),
+    (RenderStyle::FinalAngle, "Final Angle"),
+    (RenderStyle::DistanceSum, "Distance Sum"),
+    (RenderStyle::NormSum, "Norm Sum"),
+    (RenderStyle::FinalNorm, "Final Norm"),
+    (RenderStyle::AngleSum, "Angle Sum"),
+    (RenderStyle::DistanceEstimate, "Distance Estimate"),
+    (RenderStyle::StripeAverage, "Stripe Average"),
+    (
+        RenderStyle::TriangleInequalityAverage,
+        "Triangle Inequality Average",
+    ),
+    (RenderStyle::OrbitTrap, "Orbit Trap"),
+    (RenderStyle::ImageTrap, "Image Trap"),
+    (RenderStyle::Period, "Period"),
+    (RenderStyle::MultiplierNorm, "Multiplier Norm"),
+    (RenderStyle::MultiplierAngle, "Multiplier Angle"),
+    (RenderStyle::BinaryDecomposition, "Binary Decomposition"),
+    (RenderStyle::AtomDomain, "Atom Domain"),
+    (RenderStyle::Root, "Root"),
+];
+
+pub(super) fn style_combo(ui: &mut egui::Ui, id_salt: &str, style: &mut RenderStyle) {
+    egui::ComboBox::from_id_salt(id_salt)
+        .selected_text(format!("{style:?}"))
+        .show_ui(ui, |ui| {
+            for (value, label) in STYLES {
+                ui.selectable_value(style, value, label);
+            }
+        });
+}
+
+const FORMULAS: [(Formula, &str); 9] = [
+    (Formula::Mandelbrot, "Mandelbrot"),
+    (Formula::BurningShip, "Burning Ship"),
+    (
+        Formula::PerpendicularBurningShip,
+        "Perpendicular Burning Ship",
+    ),
+    (Formula::Celtic, "Celtic"),
+    (Formula::PerpendicularCeltic, "Perpendicular Celtic"),
+    (Formula::Buffalo, "Buffalo"),
+    (Formula::PerpendicularBuffalo, "Perpendicular Buffalo"),
+    (Formula::Tricorn, "Tricorn"),
+    (Formula::Newton, "Newton"),
+];
+
+const TRANSFERS: [(Transfer, &str); 6] = [
+    (Transfer::Linear, "Linear"),
+    (Transfer::Log, "Log"),
+    (Transfer::Sqrt, "Square Root"),
+    (Transfer::Cbrt, "Cube Root"),
+    (Transfer::Power, "Power"),
+    (Transfer::Rank, "Rank"),
+];
+
+/// The transfer function, and the exponent of `Power`. Only the outside can be ranked, as the
+/// histogram is gathered from it
+pub(super) fn transfer_ui(ui: &mut egui::Ui, id_salt: &str, colouring: &mut Colouring, rank: bool) {
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt(id_salt)
+            .selected_text(format!("{:?}", colouring.transfer))
+            .show_ui(ui, |ui| {
+                for (value, label) in TRANSFERS {
+                    ui.add_enabled_ui(rank || value != Transfer::Rank, |ui| {
+                        ui.selectable_value(&mut colouring.transfer, value, label)
+                    });
+                }
+            });
+        if colouring.transfer == Transfer::Power {
+            ui.add(
+                egui::DragValue::new(&mut colouring.exponent)
+                    .range(0.05..=8.0)
+                    .speed(0.01),
+            );
+        }
+    });
+}
+
+pub(super) fn wrap_combo(ui: &mut egui::Ui, id_salt: &str, wrap: &mut Wrap) {
+    egui::ComboBox::from_id_salt(id_salt)
+        .selected_text(format!("{wrap:?}"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(wrap, Wrap::Repeat, "Repeat");
+            ui.selectable_value(wrap, Wrap::Mirror, "Mirror");
+            ui.selectable_value(wrap, Wrap::Clamp, "Clamp");
+        });
+}
@@ -0,0 +1,128 @@
+use shared::complex::Complex;
+use shared::formula::*;
+use shared::push_constants::shader::FragmentConstants;
+use spirv_std::glam::*;
+
+const MAX_PERIOD: u32 = 64;
+const NEWTON_STEPS: u32 = 8;
+/// How close the orbit has to come back to itself to be considered periodic
+const PERIOD_EPSILON_SQ: f32 = 1e-6;
+/// Below this `|f^p'(z) - 1|^2` the Newton step is too ill-conditioned to take
+const NEWTON_EPSILON_SQ: f32 = 1e-12;
+
+/// The attracting cycle an interior orbit converges to
+#[derive(Clone, Copy, Default)]
+pub struct Cycle {
+    /// Zero when no cycle was found
+    pub period: u32,
+    /// Derivative of `f^period` at the cycle, with a norm below one
+    pub multiplier: Vec2,
+    /// Estimated distance to the boundary of the hyperbolic component in the parameter plane
+    pub distance: f32,
+}
+
+/// The map of `constants.formula` at `c`, with its derivatives with respect to `z`. Only used
+/// for analytic maps whose powers are the same on every branch, so no argument is carried over
+#[derive(Clone, Copy)]
+struct Map<'a> {
+    constants: &'a FragmentConstants,
+    newton: &'a Newton,
+    c: Complex,
+}
+
+impl Map<'_> {
+    fn f(self, z: Complex) -> Complex {
+        self.constants
+            .formula
+            .step(self.constants, self.newton, z, self.c, &mut 0.0)
+    }
+
+    /// `f(z)` along with its first and second derivative with respect to `z`
+    fn derivatives(self, z: Complex) -> (Complex, Complex, Complex) {
+        let formula = self.constants.formula;
+        let one = Complex::new(1.0, 0.0);
+        (
+            self.f(z),
+            formula.derivative(self.constants, self.newton, z, one, Complex::ZERO, 0.0),
+            formula.second_derivative(self.constants, z, 0.0),
+        )
+    }
+
+    /// Iterates `p` times from `z` and returns `f^p(z)` and its derivative with respect to `z`
+    fn iterate(self, z: Complex, p: u32) -> (Complex, Complex) {
+        let mut z = z;
+        let mut dz = Complex::new(1.0, 0.0);
+        for _ in 0..p {
+            let (fz, dfz, _) = self.derivatives(z);
+            dz = dfz * dz;
+            z = fz;
+        }
+        (z, dz)
+    }
+}
+
+/// Finds the attracting cycle near `z`, the end of an orbit at `c` that stayed bounded
+pub fn attracting_cycle(constants: &FragmentConstants, newton: &Newton, z: Vec2, c: Vec2) -> Cycle {
+    let one = Complex::new(1.0, 0.0);
+    let map = Map {
+        constants,
+        newton,
+        c: Complex::from(c),
+    };
+    let z = Complex::from(z);
+    let mut period = 0;
+    let mut w = z;
+    for p in 1..MAX_PERIOD + 1 {
+        w = map.f(w);
+        if (w - z).abs_sq() < PERIOD_EPSILON_SQ * (1.0 + z.abs_sq()) {
+            period = p;
+            break;
+        }
+    }
+    if period == 0 {
+        return Cycle::default();
+    }
+
+    // Newton's method on `f^p(z) - z` to land exactly on the cycle
+    let mut z0 = z;
+    for _ in 0..NEWTON_STEPS {
+        let (zp, dz) = map.iterate(z0, period);
+        let slope = dz - one;
+        if slope.abs_sq() < NEWTON_EPSILON_SQ {
+            break;
+        }
+        let next = z0 - (zp - z0) / slope;
+        if !Vec2::from(next).is_finite() {
+            break;
+        }
+        z0 = next;
+    }
+
+    // Derivatives of `f^p` at the cycle with respect to `z` and `c`
+    let mut z = z0;
+    let mut dz = one;
+    let mut dc = Complex::ZERO;
+    let mut dzdz = Complex::ZERO;
+    let mut dcdz = Complex::ZERO;
+    for _ in 0..period {
+        let (fz, dfz, ddfz) = map.derivatives(z);
+        dcdz = ddfz * dc * dz + dfz * dcdz;
+        dzdz = ddfz * dz * dz + dfz * dzdz;
+        dc = dfz * dc + one;
+        dz = dfz * dz;
+        z = fz;
+    }
+    let multiplier = Vec2::from(dz);
+    if !multiplier.is_finite() || multiplier.length_squared() >= 1.0 {
+        return Cycle::default();
+    }
+    let distance = (1.0 - dz.abs_sq()) / (dcdz + dzdz * dc / (one - dz)).abs();
+    if !distance.is_finite() {
+        return Cycle::default();
+    }
+    Cycle {
+        period,
+        multiplier,
+        distance,
+    }
+}
@@ -1,118 +1,156 @@
 #![no_std]
 
 use push_constants::shader::*;
+use shared::colour::*;
 use shared::complex::Complex;
+use shared::formula::*;
 use shared::grid::*;
+use shared::orbit::Pixel;
 use shared::*;
 use spirv_std::glam::*;
+use spirv_std::image::Image2d;
 #[cfg(target_arch = "spirv")]
 use spirv_std::num_traits::real::Real;
-use spirv_std::spirv;
+use spirv_std::{Sampler, spirv};
 
-mod palette;
+mod interior;
 mod sdf;
 
-fn get_col(palette: Palette, x: f32) -> Vec3 {
-    match palette {
-        Palette::RGB => palette::rgb(x),
-        Palette::Zebra => palette::zebra(x),
-        Palette::Copper => palette::copper(x),
-        Palette::NeonA => palette::neon_a(x),
-        Palette::SolarizedDark => palette::solarized_dark(x),
-        Palette::Highlighter => palette::highlighter(x),
-        Palette::Pastel => palette::pastel(x),
-        Palette::RedAndBlack => palette::red_and_black(x),
-        Palette::NeonB => palette::neon_b(x),
-        Palette::NeonC => palette::neon_c(x),
+trait Mandelbrot {
+    fn c(&self, constants: &FragmentConstants) -> Complex;
+    /// Continues `orbit` for at most `constants.iterations_per_frame` iterations, calling `f`
+    /// with each new `z`
+    fn iterate<F: FnMut(Complex)>(self, constants: &FragmentConstants, orbit: &mut Orbit, f: F);
+}
+
+/// A pixel's orbit as far as it has been iterated, gathered from the grids that keep it between
+/// frames
+#[derive(Clone, Copy)]
+struct Orbit {
+    end: OrbitEnd,
+    features: OrbitFeatures,
+    sums: OrbitSums,
+    state: IterationState,
+}
+
+impl Orbit {
+    fn new(z0: Complex, der0: Complex) -> Self {
+        Self {
+            end: OrbitEnd {
+                zs: [Vec2::ZERO, Vec2::ZERO, z0.into()],
+            },
+            features: OrbitFeatures::new(der0.into()),
+            sums: OrbitSums::new(),
+            state: IterationState::default(),
+        }
     }
 }
 
-trait Mandelbrot {
-    fn z0(&self) -> Complex;
-    fn iterate<F: FnMut(Complex)>(self, constants: &FragmentConstants, f: F) -> MandelbrotResult;
+/// Marks the orbit as finished once it has bailed out or run out of iterations
+fn finish(orbit: &mut Orbit, num_iters: u32, bailed_out: bool) {
+    orbit.state.finished = (bailed_out || orbit.features.i == num_iters).into();
 }
 
-struct MandelbrotResult {
-    inside: bool,
-    i: u32,
-    h: f32,
+fn slice_end(constants: &FragmentConstants, i: u32, num_iters: u32) -> u32 {
+    num_iters.min(i + constants.iterations_per_frame.min(num_iters))
 }
 
-struct RegularMandelbrot {
-    z0: Complex,
+#[derive(Clone, Copy)]
+struct RegularMandelbrot<'a> {
     c: Complex,
+    julia: bool,
+    newton: &'a Newton,
 }
 
-impl Mandelbrot for RegularMandelbrot {
-    fn z0(&self) -> Complex {
-        self.z0
+impl Mandelbrot for RegularMandelbrot<'_> {
+    fn c(&self, _constants: &FragmentConstants) -> Complex {
+        self.c
     }
 
     fn iterate<F: FnMut(Complex)>(
         self,
         constants: &FragmentConstants,
+        orbit: &mut Orbit,
         mut f: F,
-    ) -> MandelbrotResult {
-        let RegularMandelbrot { z0: mut z, c } = self;
+    ) {
+        let RegularMandelbrot { c, julia, newton } = self;
+        let formula = constants.formula;
+        let der_c = if julia { 0.0 } else { 1.0 };
         let num_iters = constants.num_iterations as u32 + 1;
-        let mut i = 0;
-        let mut prev_norm_sq = 0.0;
-        let mut norm_sq = z.abs_sq();
-        while norm_sq < constants.escape_radius_sq() && i < num_iters {
-            if constants.exponent == 2.0 {
-                z = z * z + c;
-            } else {
-                z = z.powf(constants.exponent).to_rectangular() + c;
-            }
-            prev_norm_sq = norm_sq;
-            norm_sq = z.abs_sq();
+        let end = slice_end(constants, orbit.features.i, num_iters);
+        let mut prev_prev_z: Complex = orbit.end.zs[0].into();
+        let mut prev_z: Complex = orbit.end.zs[1].into();
+        let mut z: Complex = orbit.end.zs[2].into();
+        let mut der: Complex = Vec2::from_array(orbit.features.der).into();
+        let mut i = orbit.features.i;
+        let mut arg = orbit.state.arg;
+        // Converging takes at least one step
+        let mut bailed_out = (i > 0 || !formula.is_root_finding())
+            && formula.has_bailed_out(constants, prev_z.into(), z.into());
+        while !bailed_out && i < end {
+            prev_prev_z = prev_z;
+            prev_z = z;
+            der = formula.derivative(constants, newton, z, der, Complex::new(der_c, 0.0), arg);
+            z = formula.step(constants, newton, z, c, &mut arg);
+            bailed_out = formula.has_bailed_out(constants, prev_z.into(), z.into());
             i += 1;
             f(z);
         }
 
-        let h = get_proximity(prev_norm_sq.sqrt(), norm_sq.sqrt(), constants.escape_radius);
-        let inside = i == num_iters
-            && (norm_sq < constants.escape_radius_sq() || h > constants.num_iterations.fract());
-        MandelbrotResult { inside, i, h }
+        let der: Vec2 = der.into();
+        orbit.end.zs = [prev_prev_z.into(), prev_z.into(), z.into()];
+        orbit.features.der = der.to_array();
+        orbit.features.i = i;
+        orbit.state.arg = arg;
+        finish(orbit, num_iters, bailed_out);
     }
 }
 
+#[derive(Clone, Copy)]
 struct PerturbedMandelbrot<'a> {
-    z0: Complex,
-    dz: Complex,
     dc: Complex,
     reference_points: &'a [Complex],
     num_ref_iterations: usize,
+    newton: &'a Newton,
 }
 
 impl Mandelbrot for PerturbedMandelbrot<'_> {
-    fn z0(&self) -> Complex {
-        self.z0
+    fn c(&self, constants: &FragmentConstants) -> Complex {
+        Complex::from(constants.mandelbrot_camera_translate) + self.dc
     }
 
     fn iterate<F: FnMut(Complex)>(
         self,
         constants: &FragmentConstants,
+        orbit: &mut Orbit,
         mut f: F,
-    ) -> MandelbrotResult {
+    ) {
         let PerturbedMandelbrot {
-            z0,
-            mut dz,
             dc,
             reference_points,
             num_ref_iterations,
+            newton,
         } = self;
+        let formula = constants.formula;
         let num_iters = constants.num_iterations as u32 + 1;
-        let mut i = 0;
-        let mut prev_norm_sq = 0.0;
-        let mut norm_sq = z0.abs_sq();
-        let mut ref_i = 0;
+        let end = slice_end(constants, orbit.features.i, num_iters);
+        let mut prev_prev_z: Complex = orbit.end.zs[0].into();
+        let mut prev_z: Complex = orbit.end.zs[1].into();
+        let mut z: Complex = orbit.end.zs[2].into();
+        let mut dz: Complex = orbit.state.dz.into();
+        let mut der: Complex = Vec2::from_array(orbit.features.der).into();
+        let mut i = orbit.features.i;
+        let mut norm_sq = z.abs_sq();
+        let mut ref_i = orbit.state.ref_i as usize;
 
-        while norm_sq < constants.escape_radius_sq() && i < num_iters {
-            dz = 2.0 * reference_points[ref_i] * dz + dz * dz + dc;
+        while norm_sq < constants.escape_radius_sq() && i < end {
+            // Only the quadratic maps are perturbed, which need no branch
+            der = formula.derivative(constants, newton, z, der, Complex::new(1.0, 0.0), 0.0);
+            dz = formula.perturb(reference_points[ref_i], dz, dc);
             ref_i += 1;
-            let z = reference_points[ref_i] + dz;
-            prev_norm_sq = norm_sq;
+            prev_prev_z = prev_z;
+            prev_z = z;
+            z = reference_points[ref_i] + dz;
             norm_sq = z.abs_sq();
             i += 1;
             f(z);
@@ -122,30 +160,167 @@ impl Mandelbrot for PerturbedMandelbrot<'_> {
             }
         }
 
-        let h = get_proximity(prev_norm_sq.sqrt(), norm_sq.sqrt(), constants.escape_radius);
-        let inside = i == num_iters
-            && (norm_sq < constants.escape_radius_sq() || h > constants.num_iterations.fract());
-        MandelbrotResult { inside, i, h }
+        let der: Vec2 = der.into();
+        orbit.end.zs = [prev_prev_z.into(), prev_z.into(), z.into()];
+        orbit.state.dz = dz.into();
+        orbit.features.der = der.to_array();
+        orbit.features.i = i;
+        orbit.state.ref_i = ref_i as u32;
+        finish(orbit, num_iters, norm_sq >= constants.escape_radius_sq());
+    }
+}
+
+/// The image sampled by image orbit traps
+#[derive(Clone, Copy)]
+struct TrapTexture<'a> {
+    image: &'a Image2d,
+    sampler: Sampler,
+}
+
+impl TrapTexture<'_> {
+    fn sample(&self, uv: Vec2) -> Vec4 {
+        self.image.sample_by_lod(self.sampler, uv, 0.0)
     }
 }
 
-fn get_render_parameters<T: Mandelbrot>(
+/// Continues `orbit`, gathering the statistics of every style that sums over it
+fn iterate_orbit<T: Mandelbrot>(
     constants: &FragmentConstants,
     mandelbrot_input: T,
-) -> RenderParameters {
-    let render_parameter_builder = RenderParameterBuilder {
-        constants,
-        mandelbrot_input,
+    orbit: &mut Orbit,
+    trap_texture: TrapTexture,
+) {
+    let c_norm = mandelbrot_input.c(constants).abs();
+    let trap = constants.orbit_trap;
+    let ImageTrap {
+        aspect,
+        mode,
+        opacity,
+    } = constants.image_trap;
+    let mut n = orbit.features.i;
+    let mut prev_z = orbit.end.zs[2];
+    let mut sums = orbit.sums;
+    let mut atom_period = orbit.features.atom_period;
+    // The atom domains are measured against |z_1|, which isn't known until the first iteration
+    // and is carried between frames from there
+    let mut first_norm = orbit.state.first_norm;
+    let mut atom_norm = if n > 0 {
+        orbit.features.atom_ratio * first_norm
+    } else {
+        f32::MAX
     };
-    match constants.render_style {
-        RenderStyle::Iterations => render_parameter_builder.iterations(),
-        RenderStyle::FinalAngle => render_parameter_builder.final_angle(),
-        RenderStyle::FinalDistance => render_parameter_builder.final_distance(),
-        RenderStyle::DistanceSum => render_parameter_builder.distance_sum(),
-        RenderStyle::NormSum => render_parameter_builder.norm_sum(),
-        RenderStyle::FinalNorm => render_parameter_builder.final_norm(),
-        RenderStyle::AngleSum => render_parameter_builder.angle_sum(),
+    let mut image_trap = unpack_rgba(sums.image_trap);
+    let mut hit = |z: Vec2| {
+        let uv = trap.image_uv(z, aspect);
+        if image_trap.w < 1.0 && uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all() {
+            let texel = trap_texture.sample(uv);
+            let weight = match mode {
+                ImageTrapMode::FirstHit => {
+                    if texel.w > 0.0 {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }
+                ImageTrapMode::Blend => texel.w * opacity,
+            };
+            image_trap += (1.0 - image_trap.w) * weight * texel.xyz().extend(1.0);
+        }
+    };
+    if n == 0 {
+        hit(prev_z);
+    }
+    mandelbrot_input.iterate(constants, orbit, |z| {
+        let z: Vec2 = z.into();
+        n += 1;
+        sums.add(constants, c_norm, n, prev_z, z);
+        hit(z);
+        let norm = z.length();
+        if n == 1 {
+            first_norm = norm;
+        }
+        if norm < atom_norm {
+            atom_norm = norm;
+            atom_period = n;
+        }
+        prev_z = z;
+    });
+    sums.image_trap = pack_rgba(image_trap);
+    orbit.sums = sums;
+    orbit.features.atom_period = atom_period;
+    if n > 0 {
+        orbit.features.atom_ratio = atom_norm / first_norm.max(f32::MIN_POSITIVE);
+    }
+    orbit.state.first_norm = first_norm;
+}
+
+/// Starts or resumes the iterations of the pixel at `p`, keeping what the styles need to colour
+/// it in the grids, which also hold the orbit between the frames it is spread over
+fn iterate_pixel<T: Mandelbrot>(
+    constants: &FragmentConstants,
+    newton: &Newton,
+    mandelbrot_input: T,
+    z0: Complex,
+    der0: Complex,
+    needs_reiterate: bool,
+    p: UVec2,
+    grid: &mut [OrbitEnd],
+    feature_grid: &mut [OrbitFeatures],
+    sum_grid: &mut [OrbitSums],
+    iteration_states: &mut [IterationState],
+    trap_texture: TrapTexture,
+) -> (OrbitEnd, OrbitFeatures, OrbitSums) {
+    let time_sliced = constants.time_sliced.into();
+    let grid_size = constants.size.as_uvec2();
+    let mut state_grid = GridRefMut::new(grid_size, iteration_states);
+    let mut end_grid = GridRefMut::new(grid_size, grid);
+    let mut feature_grid = GridRefMut::new(grid_size, feature_grid);
+    let mut sum_grid = GridRefMut::new(grid_size, sum_grid);
+    let cached = end_grid.as_ref().contains(p);
+    let mut orbit = if needs_reiterate || !time_sliced || !cached {
+        Orbit::new(z0, der0)
+    } else {
+        Orbit {
+            end: end_grid.get(p),
+            features: feature_grid.get(p),
+            sums: sum_grid.get(p),
+            state: state_grid.get(p),
+        }
+    };
+    if orbit.state.finished.into() {
+        return (orbit.end, orbit.features, orbit.sums);
+    }
+    let c = mandelbrot_input.c(constants);
+    iterate_orbit(constants, mandelbrot_input, &mut orbit, trap_texture);
+    if time_sliced && cached {
+        state_grid.set(p, orbit.state);
+    }
+
+    let zs = orbit.end.zs;
+    if orbit.state.finished.into() {
+        // The cycle analysis takes fractional powers on the principal branch
+        let exponent = constants.exponent;
+        let analytic = constants.formula.is_analytic()
+            && exponent.im == 0.0
+            && (exponent.branch == Branch::Principal || exponent.is_integer());
+        let bailed_out = constants.formula.has_bailed_out(constants, zs[1], zs[2]);
+        if analytic && !bailed_out {
+            // Bounded orbits have no use for the derivative, so the multiplier of the cycle they
+            // end on takes its place
+            let cycle = interior::attracting_cycle(constants, newton, zs[2], c.into());
+            if cycle.period > 0 {
+                orbit.features.der = cycle.multiplier.to_array();
+                orbit.features.cycle_period = cycle.period;
+                orbit.features.cycle_distance = cycle.distance;
+            }
+        }
+    }
+    if cached {
+        end_grid.set(p, orbit.end);
+        feature_grid.set(p, orbit.features);
+        sum_grid.set(p, orbit.sums);
     }
+    (orbit.end, orbit.features, orbit.sums)
 }
 
 #[spirv(fragment)]
@@ -160,9 +335,21 @@ pub fn main_fs(
     #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] iteration_points: &[Vec2],
     #[spirv(storage_buffer, descriptor_set = 0, binding = 1)]
     mandelbrot_reference_points: &[Complex],
-    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] grid: &mut [RenderParameters],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] grid: &mut [OrbitEnd],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)]
+    iteration_states: &mut [IterationState],
+    #[spirv(descriptor_set = 0, binding = 4)] trap_image: &Image2d,
+    #[spirv(descriptor_set = 0, binding = 5)] trap_sampler: &Sampler,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 6)] colourings: &Colourings,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 7)] feature_grid: &mut [OrbitFeatures],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 8)] sum_grid: &mut [OrbitSums],
     output: &mut Vec4,
 ) {
+    let newton = &colourings.newton;
+    let trap_texture = TrapTexture {
+        image: trap_image,
+        sampler: *trap_sampler,
+    };
     let coord = frag_coord.xy();
     let size = constants.size.as_vec2();
     let is_split_vertical = size.x > size.y;
@@ -173,46 +360,99 @@ pub fn main_fs(
         (coord - 0.5 * size) / size.y / mandelbrot_zoom + constants.mandelbrot_camera_translate;
     let is_julia = render_julia_set && coord.dot(n) > size.dot(n) * constants.render_split;
 
-    let render_parameters = if constants.needs_reiterate_mandelbrot.into() && !is_julia {
+    let p = coord.as_uvec2();
+    // The grids are allocated for the window size they were bound at, and pixels past their
+    // end, once the window has grown, are iterated from the start every frame
+    let cached = GridRef::new(constants.size.as_uvec2(), grid).contains(p);
+    let (end, features, sums) = if (constants.needs_reiterate_mandelbrot.into()
+        || constants.resume_mandelbrot.into()
+        || !cached)
+        && !is_julia
+    {
+        let needs_reiterate = constants.needs_reiterate_mandelbrot.into();
         let dc = (coord - 0.5 * size) / size.y / mandelbrot_zoom;
-        let render_parameters = if constants.iteration_mode == IterationMode::Regular {
-            get_render_parameters(
+        if constants.iteration_mode == IterationMode::Regular {
+            let c: Complex = (dc + constants.mandelbrot_camera_translate).into();
+            // Root finding starts from the pixel, as julia sets do
+            let root_finding = constants.formula.is_root_finding();
+            let (z0, der0) = if root_finding {
+                (c, Complex::new(1.0, 0.0))
+            } else {
+                (Complex::ZERO, Complex::ZERO)
+            };
+            iterate_pixel(
                 constants,
+                newton,
                 RegularMandelbrot {
-                    z0: Complex::ZERO,
-                    c: (dc + constants.mandelbrot_camera_translate).into(),
+                    c,
+                    julia: root_finding,
+                    newton,
                 },
+                z0,
+                der0,
+                needs_reiterate,
+                p,
+                grid,
+                feature_grid,
+                sum_grid,
+                iteration_states,
+                trap_texture,
             )
         } else {
-            get_render_parameters(
+            iterate_pixel(
                 constants,
+                newton,
                 PerturbedMandelbrot {
-                    z0: Complex::ZERO,
-                    dz: Complex::ZERO,
                     dc: dc.into(),
                     reference_points: mandelbrot_reference_points,
                     num_ref_iterations: constants.mandelbrot_num_ref_iterations as usize,
+                    newton,
                 },
+                Complex::ZERO,
+                Complex::ZERO,
+                needs_reiterate,
+                p,
+                grid,
+                feature_grid,
+                sum_grid,
+                iteration_states,
+                trap_texture,
             )
-        };
-
-        let mut cell_grid = GridRefMut::new(GRID_SIZE, grid);
-        cell_grid.set(coord.as_uvec2(), render_parameters);
-        render_parameters
-    } else if constants.needs_reiterate_julia.into() && is_julia {
+        }
+    } else if (constants.needs_reiterate_julia.into() || constants.resume_julia.into() || !cached)
+        && is_julia
+    {
         let z0 = ((coord - 0.5 * size) / size.y / constants.julia_camera_zoom
             + constants.julia_camera_translate)
             .into();
         let c: Complex = constants.marker.into();
-        let render_parameters = get_render_parameters(constants, RegularMandelbrot { z0, c });
-        let mut cell_grid = GridRefMut::new(GRID_SIZE, grid);
-        cell_grid.set(coord.as_uvec2(), render_parameters);
-        render_parameters
+        iterate_pixel(
+            constants,
+            newton,
+            RegularMandelbrot {
+                c,
+                julia: true,
+                newton,
+            },
+            z0,
+            Complex::new(1.0, 0.0),
+            constants.needs_reiterate_julia.into(),
+            p,
+            grid,
+            feature_grid,
+            sum_grid,
+            iteration_states,
+            trap_texture,
+        )
     } else {
-        let cell_grid = GridRef::new(GRID_SIZE, grid);
-        cell_grid.get(coord.as_uvec2())
+        let grid_size = constants.size.as_uvec2();
+        let end_grid = GridRef::new(grid_size, grid);
+        let feature_grid = GridRef::new(grid_size, feature_grid);
+        let sum_grid = GridRef::new(grid_size, sum_grid);
+        (end_grid.get(p), feature_grid.get(p), sum_grid.get(p))
     };
-    let mut col = col_from_render_parameters(constants, render_parameters);
+    let pixel = Pixel::new(constants, coord);
+    let mut col = col_from_orbit(constants, colourings, pixel, end, features, sums);
 
     // Slider
     if render_julia_set {
@@ -223,6 +463,38 @@ pub fn main_fs(
         col += Vec3::ONE * intensity;
     }
 
+    // Orbit trap
+    let partitioning = constants.render_partitioning;
+    let orbit_trap = colourings.uses_style(partitioning, RenderStyle::OrbitTrap);
+    if (orbit_trap || colourings.uses_style(partitioning, RenderStyle::ImageTrap)) && !is_julia {
+        let trap = constants.orbit_trap;
+        let d = if orbit_trap {
+            trap.distance(mandelbrot_uv)
+        } else {
+            let half_size = 0.5 * trap.size * vec2(constants.image_trap.aspect, 1.0);
+            let position = Vec2::from_array(trap.position);
+            let p = Vec2::from_angle(-trap.angle).rotate(mandelbrot_uv - position);
+            sdf::rectangle(p, half_size).abs()
+        };
+        let d = d * mandelbrot_zoom * size.y;
+        col += 0.5 * smoothstep(1.5, 0.0, d);
+        let screen_space = Vec2::from_array(constants.orbit_trap_screen_space);
+        let d = sdf::disk(coord - screen_space, MARKER_RADIUS);
+        col += smoothstep(3.0, 0.0, d.abs());
+    }
+
+    // Roots of the Newton polynomial
+    if constants.formula.is_root_finding() && !is_julia {
+        for k in 0..newton.num_roots as usize {
+            let root = Vec2::from_array(newton.roots[k]);
+            let d = sdf::disk(
+                (mandelbrot_uv - root) * mandelbrot_zoom * size.y,
+                MARKER_RADIUS,
+            );
+            col += smoothstep(3.0, 0.0, d.abs());
+        }
+    }
+
     let show_iterations = constants.show_iterations.into();
     if (show_iterations || render_julia_set) && !is_julia {
         // Iteration line segments
@@ -248,115 +520,9 @@ pub fn main_fs(
         }
     }
 
-    *output = col.powf(2.2).extend(1.0);
-}
-
-fn col_from_render_parameters(
-    constants: &FragmentConstants,
-    RenderParameters { i, x }: RenderParameters,
-) -> Vec3 {
-    if i == core::u32::MAX {
-        return Vec3::ZERO;
-    }
-    let period = constants.palette_period;
-    let t = constants.animate_time;
-    let (period, t) = match constants.render_style {
-        RenderStyle::Iterations => (0.3 * period, -t),
-        RenderStyle::FinalAngle => (period, -t),
-        RenderStyle::FinalDistance => (period, t),
-        RenderStyle::DistanceSum => (0.2 * period, t),
-        RenderStyle::NormSum => (0.3 * period, t),
-        RenderStyle::AngleSum => (0.3 * period, t),
-        _ => (period, t),
-    };
-    get_col(constants.palette, x * period + t)
-}
-
-struct RenderParameterBuilder<'a, T> {
-    constants: &'a FragmentConstants,
-    mandelbrot_input: T,
-}
-
-impl<T: Mandelbrot> RenderParameterBuilder<'_, T> {
-    fn iterations(self) -> RenderParameters {
-        let MandelbrotResult { inside, i, h } =
-            self.mandelbrot_input.iterate(self.constants, |_| {});
-        let x0 = i as f32;
-        let x1 = (i + 1) as f32;
-        RenderParameters::new(self.constants, inside, i, h, x0, x1)
-    }
-
-    fn final_angle(self) -> RenderParameters {
-        let mut zs = [Complex::ZERO, self.mandelbrot_input.z0()];
-        let MandelbrotResult { inside, i, h } =
-            self.mandelbrot_input.iterate(self.constants, |z| {
-                zs[0] = zs[1];
-                zs[1] = z;
-            });
-        let angle0 = zs[0].arg().abs();
-        let angle1 = zs[1].arg().abs();
-        RenderParameters::new(self.constants, inside, i, h, angle0, angle1)
-    }
-
-    fn final_distance(self) -> RenderParameters {
-        let mut zs = [Complex::ZERO, Complex::ZERO, self.mandelbrot_input.z0()];
-        let MandelbrotResult { inside, i, h } =
-            self.mandelbrot_input.iterate(self.constants, |z| {
-                zs[0] = zs[1];
-                zs[1] = zs[2];
-                zs[2] = z;
-            });
-        let ds0 = zs[0].distance(zs[1]);
-        let ds1 = zs[1].distance(zs[2]);
-        RenderParameters::new(self.constants, inside, i, h, ds0, ds1)
-    }
-
-    fn distance_sum(self) -> RenderParameters {
-        let mut prev_z = Complex::ZERO;
-        let mut prev_dist = 0.0;
-        let mut dist = 0.0;
-        let MandelbrotResult { inside, i, h } =
-            self.mandelbrot_input.iterate(self.constants, |z| {
-                prev_dist = dist;
-                dist += prev_z.distance(z);
-                prev_z = z;
-            });
-        RenderParameters::new(self.constants, inside, i, h, prev_dist, dist)
-    }
-
-    fn norm_sum(self) -> RenderParameters {
-        let mut prev_norm_sum = 0.0;
-        let mut norm_sum = 0.0;
-        let MandelbrotResult { inside, i, h } =
-            self.mandelbrot_input.iterate(self.constants, |z| {
-                prev_norm_sum = norm_sum;
-                norm_sum += z.abs();
-            });
-        RenderParameters::new(self.constants, inside, i, h, prev_norm_sum, norm_sum)
-    }
-
-    fn final_norm(self) -> RenderParameters {
-        let mut zs = [Complex::ZERO, self.mandelbrot_input.z0()];
-        let MandelbrotResult { inside, i, h } =
-            self.mandelbrot_input.iterate(self.constants, |z| {
-                zs[0] = zs[1];
-                zs[1] = z;
-            });
-        let norm0 = zs[0].abs();
-        let norm1 = zs[1].abs();
-        RenderParameters::new(self.constants, inside, i, h, norm0, norm1)
-    }
-
-    fn angle_sum(self) -> RenderParameters {
-        let mut prev_angle_sum = 0.0;
-        let mut angle_sum = 0.0;
-        let MandelbrotResult { inside, i, h } =
-            self.mandelbrot_input.iterate(self.constants, |z| {
-                prev_angle_sum = angle_sum;
-                angle_sum += z.arg().abs();
-            });
-        RenderParameters::new(self.constants, inside, i, h, prev_angle_sum, angle_sum)
-    }
+    // The surface encodes to sRGB itself, so the dithered encoding is decoded again for it
+    let col = colour_space::encode_srgb(col, p, constants.dither);
+    *output = colour_space::srgb_to_linear(col).extend(1.0);
 }
 
 #[spirv(vertex)]
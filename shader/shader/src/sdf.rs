@@ -11,3 +11,8 @@ pub fn disk(p: Vec2, r: f32) -> f32 {
 pub fn line_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
     p.distance(a + project_onto_segment(p - a, b - a))
 }
+
+pub fn rectangle(p: Vec2, half_size: Vec2) -> f32 {
+    let d = p.abs() - half_size;
+    d.max(Vec2::ZERO).length() + d.x.max(d.y).min(0.0)
+}
@@ -0,0 +1,203 @@
+use crate::colour_space::srgb_to_linear;
+use crate::orbit::{self, Pixel};
+use crate::palette;
+use crate::push_constants::shader::*;
+use crate::{HISTOGRAM_SIZE, smoothstep};
+use glam::*;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::real::Real;
+
+pub fn get_col(palette: Palette, colourings: &Colourings, x: f32) -> Vec3 {
+    match palette {
+        Palette::Custom => palette::gradient(
+            |k| colourings.gradient[k],
+            colourings.num_gradient_stops,
+            colourings.gradient_interpolation,
+            x,
+        ),
+        _ => palette.cosine(colourings.cosine_palette).col(x),
+    }
+}
+
+pub fn pack_rgba(col: Vec4) -> u32 {
+    let c = (col.clamp(Vec4::ZERO, Vec4::ONE) * 255.0)
+        .round()
+        .as_uvec4();
+    c.x | c.y << 8 | c.z << 16 | c.w << 24
+}
+
+pub fn unpack_rgba(packed: u32) -> Vec4 {
+    (uvec4(packed, packed >> 8, packed >> 16, packed >> 24) & 0xff).as_vec4() / 255.0
+}
+
+/// Fraction of the view's colour values that are smaller than `x`, given `quantiles` sampled at
+/// evenly spaced ranks of the sorted values
+pub fn equalize(quantiles: &[f32; HISTOGRAM_SIZE as usize], x: f32) -> f32 {
+    let last = HISTOGRAM_SIZE as usize - 1;
+    if x <= quantiles[0] {
+        return 0.0;
+    }
+    if x >= quantiles[last] {
+        return 1.0;
+    }
+    let mut lo = 0;
+    let mut hi = last;
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if quantiles[mid] <= x {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    let t = (x - quantiles[lo]) / (quantiles[hi] - quantiles[lo]).max(f32::EPSILON);
+    (lo as f32 + t.clamp(0.0, 1.0)) / last as f32
+}
+
+/// `x` reshaped by the transfer function of `colouring`
+fn transfer(colouring: Colouring, colourings: &Colourings, x: f32) -> f32 {
+    // Odd, so that negative values like log distances keep their order
+    match colouring.transfer {
+        Transfer::Linear => x,
+        Transfer::Log => x.signum() * x.abs().ln_1p(),
+        Transfer::Sqrt => x.signum() * x.abs().sqrt(),
+        Transfer::Cbrt => x.cbrt(),
+        Transfer::Power => x.signum() * x.abs().powf(colouring.exponent),
+        Transfer::Rank => equalize(&colourings.histogram, x),
+    }
+}
+
+/// The colour of `x` and `y` in linear light, as coloured by `colouring`. Palettes are in sRGB,
+/// but shading and compositing happen after converting to linear
+fn style_col(
+    constants: &FragmentConstants,
+    colourings: &Colourings,
+    colouring: Colouring,
+    x: f32,
+    y: f32,
+    trap_colour: u32,
+) -> Vec3 {
+    let style = colouring.style;
+    let period = colouring.period;
+    let t = constants.animate_time;
+    let (period, t) = match style {
+        RenderStyle::Iterations | RenderStyle::BinaryDecomposition => (0.3 * period, -t),
+        RenderStyle::FinalAngle => (period, -t),
+        RenderStyle::FinalDistance => (period, t),
+        RenderStyle::DistanceSum => (0.2 * period, t),
+        RenderStyle::NormSum => (0.3 * period, t),
+        RenderStyle::AngleSum => (0.3 * period, t),
+        // Spread consecutive periods far apart on the palette
+        RenderStyle::Period | RenderStyle::AtomDomain | RenderStyle::Root => (0.381966 * period, t),
+        RenderStyle::MultiplierNorm | RenderStyle::MultiplierAngle => (2.0 * period, t),
+        _ => (period, t),
+    };
+    // Ranks are spread over a number of cycles of the palette instead
+    let scale = if colouring.transfer == Transfer::Rank {
+        constants.histogram_cycles
+    } else {
+        period
+    };
+    let position = transfer(colouring, colourings, x) * scale + t + colouring.offset;
+    let position = colouring.wrap.apply(position, colouring.palette.period());
+    let col = get_col(colouring.palette, colourings, position);
+    let mut col = srgb_to_linear(col);
+    if style == RenderStyle::DistanceEstimate {
+        // `x` is the log of the distance to the boundary in pixels
+        col *= smoothstep(0.0, constants.distance_estimate_thickness, x.exp());
+    }
+    if style == RenderStyle::BinaryDecomposition && y != 0.0 {
+        let escape_angle = y;
+        col *= if constants.field_lines.into() {
+            // Rays run along the real axis of each band, equipotentials along the band edges. The
+            // rays follow the angle of the final `z` rather than a continuous external angle, so
+            // they only approximate field lines: every band has twice the rays of the one outside
+            // it, and the new ones start at the band edge
+            let width = constants.field_line_width;
+            let ray = smoothstep(0.0, width, escape_angle.sin().abs());
+            let band = x.fract();
+            let equipotential = if constants.smooth_factor > 0.0 {
+                smoothstep(0.0, width, band.min(1.0 - band))
+            } else {
+                1.0
+            };
+            ray * equipotential
+        } else if escape_angle > 0.0 {
+            1.0
+        } else {
+            0.25
+        };
+    }
+    if style == RenderStyle::AtomDomain {
+        // Darkest at the nucleus, where the orbit passes through zero
+        col *= y.sqrt();
+    }
+    if style == RenderStyle::Root {
+        // Darker the more iterations the orbit took to converge
+        col *= 1.0 / (1.0 + 0.1 * y);
+    }
+    if style == RenderStyle::ImageTrap {
+        // The palette shows through wherever the orbit missed the opaque parts of the image
+        let trap_colour = unpack_rgba(trap_colour);
+        if trap_colour.w > 0.0 {
+            let rgb = srgb_to_linear(trap_colour.xyz() / trap_colour.w) * trap_colour.w;
+            col = col * (1.0 - trap_colour.w) + rgb;
+        }
+    }
+    col
+}
+
+/// The colour of a pixel in linear light, with its layers blended over the base colouring in
+/// order
+pub fn col_from_orbit(
+    constants: &FragmentConstants,
+    colourings: &Colourings,
+    pixel: Pixel,
+    end: OrbitEnd,
+    features: OrbitFeatures,
+    sums: OrbitSums,
+) -> Vec3 {
+    let inside = orbit::is_inside(constants, end, features.i);
+    if !constants.render_partitioning.shows(inside) {
+        return Vec3::ZERO;
+    }
+    let colouring = colourings.colouring(inside);
+    let RenderParameters {
+        x,
+        normal_angle,
+        trap_colour,
+        y,
+        ..
+    } = orbit::render_parameters(
+        constants,
+        &colourings.newton,
+        colouring.style,
+        pixel,
+        end,
+        features,
+        sums,
+    );
+    let mut col = style_col(constants, colourings, colouring, x, y, trap_colour);
+    for k in 0..colourings.num_layers as usize {
+        let layer = colourings.layers[k];
+        if layer.region.shows(inside) {
+            let RenderParameters {
+                x, y, trap_colour, ..
+            } = orbit::render_parameters(
+                constants,
+                &colourings.newton,
+                layer.colouring.style,
+                pixel,
+                end,
+                features,
+                sums,
+            );
+            let layer_col = style_col(constants, colourings, layer.colouring, x, y, trap_colour);
+            col = col.lerp(layer.blend_mode.blend(col, layer_col), layer.opacity);
+        }
+    }
+    if constants.slope_shading.into() && !inside {
+        col *= constants.light.shade(Vec2::from_angle(normal_angle));
+    }
+    col
+}
@@ -0,0 +1,127 @@
+use crate::push_constants::shader::{Dither, Interpolation};
+use core::f32::consts::{PI, TAU};
+use glam::*;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::real::Real;
+
+fn map(v: Vec3, f: impl Fn(f32) -> f32) -> Vec3 {
+    vec3(f(v.x), f(v.y), f(v.z))
+}
+
+pub fn srgb_to_linear(col: Vec3) -> Vec3 {
+    map(col, |c| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+pub fn linear_to_srgb(col: Vec3) -> Vec3 {
+    map(col, |c| {
+        if c <= 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+pub fn linear_to_oklab(col: Vec3) -> Vec3 {
+    let lms = vec3(
+        col.dot(vec3(0.41222147, 0.53633254, 0.05144599)),
+        col.dot(vec3(0.21190350, 0.68069955, 0.10739696)),
+        col.dot(vec3(0.08830246, 0.28171884, 0.62997870)),
+    );
+    let lms = map(lms, |x| x.cbrt());
+    vec3(
+        lms.dot(vec3(0.21045426, 0.79361779, -0.00407205)),
+        lms.dot(vec3(1.97799850, -2.42859221, 0.45059371)),
+        lms.dot(vec3(0.02590404, 0.78277177, -0.80867577)),
+    )
+}
+
+pub fn oklab_to_linear(lab: Vec3) -> Vec3 {
+    let lms = vec3(
+        lab.dot(vec3(1.0, 0.39633778, 0.21580376)),
+        lab.dot(vec3(1.0, -0.10556135, -0.06385417)),
+        lab.dot(vec3(1.0, -0.08948418, -1.29148555)),
+    );
+    let lms = lms * lms * lms;
+    vec3(
+        lms.dot(vec3(4.07674166, -3.30771159, 0.23096993)),
+        lms.dot(vec3(-1.26843800, 2.60975740, -0.34131940)),
+        lms.dot(vec3(-0.00419609, -0.70341861, 1.70761470)),
+    )
+}
+
+/// Lightness, chroma and hue in radians
+pub fn oklab_to_oklch(lab: Vec3) -> Vec3 {
+    vec3(lab.x, lab.yz().length(), lab.z.atan2(lab.y))
+}
+
+pub fn oklch_to_oklab(lch: Vec3) -> Vec3 {
+    let (sin, cos) = lch.z.sin_cos();
+    vec3(lch.x, lch.y * cos, lch.y * sin)
+}
+
+/// Blends from `a` to `b`, both in sRGB, giving an sRGB colour
+pub fn mix(a: Vec3, b: Vec3, s: f32, interpolation: Interpolation) -> Vec3 {
+    match interpolation {
+        Interpolation::Srgb => a.lerp(b, s),
+        Interpolation::Oklab => {
+            let a = linear_to_oklab(srgb_to_linear(a));
+            let b = linear_to_oklab(srgb_to_linear(b));
+            linear_to_srgb(oklab_to_linear(a.lerp(b, s)))
+        }
+        Interpolation::Oklch => {
+            let a = oklab_to_oklch(linear_to_oklab(srgb_to_linear(a)));
+            let b = oklab_to_oklch(linear_to_oklab(srgb_to_linear(b)));
+            // Greys have no hue of their own, so take the other's
+            let (hue_a, hue_b) = if a.y < 1e-4 {
+                (b.z, b.z)
+            } else if b.y < 1e-4 {
+                (a.z, a.z)
+            } else {
+                (a.z, b.z)
+            };
+            // The short way around
+            let mut dh = hue_b - hue_a;
+            dh -= TAU * ((dh + PI) / TAU).floor();
+            let lch = vec3(a.x + (b.x - a.x) * s, a.y + (b.y - a.y) * s, hue_a + dh * s);
+            linear_to_srgb(oklab_to_linear(oklch_to_oklab(lch)))
+        }
+    }
+}
+
+/// 8x8 Bayer matrix threshold, between 0 and 1
+fn bayer(p: UVec2) -> f32 {
+    let x = p.x & 7;
+    let v = x ^ (p.y & 7);
+    let m = ((v & 1) << 5)
+        | ((x & 1) << 4)
+        | ((v & 2) << 2)
+        | ((x & 2) << 1)
+        | ((v & 4) >> 1)
+        | ((x & 4) >> 2);
+    (m as f32 + 0.5) / 64.0
+}
+
+/// Jimenez's interleaved gradient noise, between 0 and 1
+fn interleaved_gradient_noise(p: UVec2) -> f32 {
+    let p = p.as_vec2();
+    (52.982918 * (0.06711056 * p.x + 0.00583715 * p.y).fract()).fract()
+}
+
+/// Encodes linear `col` as sRGB between 0 and 1, dithered so that rounding it to 8 bits at pixel
+/// `p` doesn't band. The shader and the exporter both go through here to give the same pixels
+pub fn encode_srgb(col: Vec3, p: UVec2, dither: Dither) -> Vec3 {
+    let threshold = match dither {
+        Dither::None => 0.5,
+        Dither::Ordered => bayer(p),
+        Dither::Noise => interleaved_gradient_noise(p),
+    };
+    let col = linear_to_srgb(col.clamp(Vec3::ZERO, Vec3::ONE));
+    (col + (threshold - 0.5) / 255.0).clamp(Vec3::ZERO, Vec3::ONE)
+}
@@ -0,0 +1,497 @@
+use crate::MAX_ROOTS;
+use crate::complex::Complex;
+use crate::get_proximity;
+use crate::push_constants::shader::FragmentConstants;
+#[cfg(not(target_arch = "spirv"))]
+use bytemuck::NoUninit;
+use core::ops::Add;
+use glam::*;
+#[cfg(not(target_arch = "spirv"))]
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::real::Real;
+
+/// The map that is iterated. The abs variants fold `z` into a quadrant before raising it to the
+/// exponent, or fold the power before adding `c`, and the tricorn reflects `z` in the real axis.
+/// Newton instead finds the roots of the polynomial given by `Newton`
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum Formula {
+    #[default]
+    Mandelbrot,
+    BurningShip,
+    PerpendicularBurningShip,
+    Celtic,
+    PerpendicularCeltic,
+    Buffalo,
+    PerpendicularBuffalo,
+    /// `conj(z)^exponent + c`, the mandelbar, with multicorns for exponents other than 2
+    Tricorn,
+    /// `z - a p(z) / p'(z)`, relaxed Newton's method on the polynomial whose roots are given,
+    /// iterated from the pixel until it converges
+    Newton,
+}
+
+impl Formula {
+    /// The fold applied to `z` before it is raised to the exponent. Folds only ever flip the
+    /// signs of the components
+    pub fn fold_z(self, z: Vec2) -> Vec2 {
+        match self {
+            Formula::BurningShip => z.abs(),
+            Formula::PerpendicularBurningShip | Formula::PerpendicularCeltic => {
+                vec2(z.x, -z.y.abs())
+            }
+            Formula::PerpendicularBuffalo => vec2(z.x.abs(), -z.y),
+            Formula::Tricorn => vec2(z.x, -z.y),
+            _ => z,
+        }
+    }
+
+    /// The fold applied to `z^exponent` before `c` is added
+    pub fn fold_power(self, w: Vec2) -> Vec2 {
+        match self {
+            Formula::Celtic | Formula::PerpendicularCeltic | Formula::PerpendicularBuffalo => {
+                vec2(w.x.abs(), w.y)
+            }
+            Formula::Buffalo => w.abs(),
+            _ => w,
+        }
+    }
+}
+
+/// Largest step at which Newton's method counts as converged
+const CONVERGENCE_TOLERANCE: f32 = 1e-4;
+
+/// The polynomial whose roots Newton's method finds, given by its roots. Read from the storage
+/// buffer of `Colourings`, which it is part of
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct Newton {
+    pub roots: [[f32; 2]; MAX_ROOTS],
+    pub num_roots: u32,
+    /// Scales each step, which is plain Newton's method at 1
+    pub relaxation: f32,
+}
+
+impl Default for Newton {
+    fn default() -> Self {
+        // The cube roots of unity
+        let mut roots = [[0.0; 2]; MAX_ROOTS];
+        for (k, root) in roots.iter_mut().enumerate().take(3) {
+            *root = Vec2::from_angle(k as f32 * core::f32::consts::TAU / 3.0).to_array();
+        }
+        Self {
+            roots,
+            num_roots: 3,
+            relaxation: 1.0,
+        }
+    }
+}
+
+impl Newton {
+    /// The sums of `1 / (z - r)` and `1 / (z - r)^2` over the roots, which are `p'(z) / p(z)`
+    /// and `(p'(z) / p(z))^2 - p''(z) / p(z)`
+    fn sums(&self, z: Complex) -> (Complex, Complex) {
+        let mut s1 = Complex::ZERO;
+        let mut s2 = Complex::ZERO;
+        for k in 0..self.num_roots as usize {
+            let recip =
+                Complex::new(1.0, 0.0) / (z - Complex::from(Vec2::from_array(self.roots[k])));
+            s1 = s1 + recip;
+            s2 = s2 + recip * recip;
+        }
+        (s1, s2)
+    }
+
+    /// The index of the root closest to `z`
+    pub fn nearest_root(&self, z: Vec2) -> u32 {
+        let mut nearest = 0;
+        let mut min_distance = f32::MAX;
+        for k in 0..self.num_roots as usize {
+            let distance = z.distance_squared(Vec2::from_array(self.roots[k]));
+            if distance < min_distance {
+                min_distance = distance;
+                nearest = k as u32;
+            }
+        }
+        nearest
+    }
+}
+
+/// How the argument of a value is picked when raising it to a non-integer power
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum Branch {
+    /// Arguments in `(-pi, pi]`, with the cut along the negative real axis
+    #[default]
+    Principal,
+    /// Arguments in `[0, 2pi)`, with the cut along the positive real axis
+    Positive,
+    /// The argument closest to the one picked at the previous iteration, so that the orbit
+    /// never jumps across a cut
+    Continuous,
+}
+
+impl Branch {
+    /// The argument of `z`, given the one picked at the previous iteration
+    pub fn arg(self, z: Vec2, prev: f32) -> f32 {
+        let arg = z.y.atan2(z.x);
+        match self {
+            Branch::Principal => arg,
+            Branch::Positive => {
+                if arg < 0.0 {
+                    arg + core::f32::consts::TAU
+                } else {
+                    arg
+                }
+            }
+            Branch::Continuous => {
+                let d = arg - prev;
+                prev + d - core::f32::consts::TAU * (d / core::f32::consts::TAU).round()
+            }
+        }
+    }
+}
+
+/// The complex exponent of the formula, and the branch its powers are taken on
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct Exponent {
+    pub re: f32,
+    pub im: f32,
+    pub branch: Branch,
+}
+
+impl Default for Exponent {
+    fn default() -> Self {
+        Self {
+            re: 2.0,
+            im: 0.0,
+            branch: Branch::Principal,
+        }
+    }
+}
+
+impl Exponent {
+    pub fn complex(self) -> Complex {
+        Complex::new(self.re, self.im)
+    }
+
+    /// Whether the exponent is a real integer, which gives the same power on every branch
+    pub fn is_integer(self) -> bool {
+        self.im == 0.0 && self.re.fract() == 0.0
+    }
+
+    /// `z^self` and `z^(self - 1)`. `arg` is the argument the branch picked at the previous
+    /// iteration, and is updated to the one picked for `z`
+    pub fn pow(self, z: Complex, arg: &mut f32) -> (Complex, Complex) {
+        if self.re == 2.0 && self.im == 0.0 {
+            return (z * z, z);
+        }
+        let z = Vec2::from(z);
+        if z == Vec2::ZERO {
+            return (
+                Complex::new(0.0f32.powf(self.re), 0.0),
+                Complex::new(0.0f32.powf(self.re - 1.0), 0.0),
+            );
+        }
+        *arg = self.branch.arg(z, *arg);
+        let ln_norm = 0.5 * z.length_squared().ln();
+        // z^(a + bi) = exp((a + bi)(ln|z| + i arg))
+        let pow = |re: f32| {
+            let norm = (re * ln_norm - self.im * *arg).exp();
+            let angle = self.im * ln_norm + re * *arg;
+            Complex::from(norm * Vec2::from_angle(angle))
+        };
+        (pow(self.re), pow(self.re - 1.0))
+    }
+}
+
+/// A complex number the quadratic maps can be iterated in, so that the reference orbits of deep
+/// zooms can be worked out in arbitrary precision
+pub trait Quadratic: Clone + Add<Output = Self> {
+    fn square(self) -> Self;
+
+    /// Applies a fold of a `Formula`, which only ever flips the signs of the components
+    fn fold(self, fold: impl Fn(Vec2) -> Vec2) -> Self;
+}
+
+impl Quadratic for Complex {
+    fn square(self) -> Self {
+        self * self
+    }
+
+    fn fold(self, fold: impl Fn(Vec2) -> Vec2) -> Self {
+        fold(self.into()).into()
+    }
+}
+
+/// One iteration `z -> f(z) + c` of a fractal. The shader, the cycle analysis, the marker orbit
+/// and the reference orbit all iterate through this, so a new fractal only needs a `Formula`
+/// variant and its arms here
+pub trait Step: Copy {
+    /// `f(z) + c`. `arg` is the argument the branch picked at the previous iteration, and is
+    /// updated to the one picked for `z`. `newton` is only read by root finding
+    fn step(
+        self,
+        constants: &FragmentConstants,
+        newton: &Newton,
+        z: Complex,
+        c: Complex,
+        arg: &mut f32,
+    ) -> Complex;
+
+    /// The derivative of `f(z) + c` given `der`, the derivative of `z`, and `der_c`, that of
+    /// `c`. `arg` is the argument the branch picked at the previous iteration
+    fn derivative(
+        self,
+        constants: &FragmentConstants,
+        newton: &Newton,
+        z: Complex,
+        der: Complex,
+        der_c: Complex,
+        arg: f32,
+    ) -> Complex;
+
+    /// The second derivative of `f(z) + c` with respect to `z`, which the analysis of attracting
+    /// cycles needs. Only analytic maps have one
+    fn second_derivative(self, constants: &FragmentConstants, z: Complex, arg: f32) -> Complex;
+
+    /// `f(z) + c` of a quadratic map in any precision, which the reference orbit is iterated
+    /// with. Only perturbable maps have one
+    fn step_quadratic<T: Quadratic>(self, z: T, c: T) -> T;
+
+    /// The next offset from the reference orbit at `reference`, given the offset `dz` from it
+    /// and `dc` from the reference `c`. Only quadratic maps are perturbed
+    fn perturb(self, reference: Complex, dz: Complex, dc: Complex) -> Complex;
+
+    /// Whether `f` is analytic, which the analysis of attracting cycles relies on
+    fn is_analytic(self) -> bool;
+
+    /// Whether the orbits start from the pixel and bail out once they converge to a root,
+    /// rather than starting from zero and bailing out once they escape
+    fn is_root_finding(self) -> bool;
+
+    /// Whether deep zooms can follow a reference orbit, which needs a quadratic map
+    fn is_perturbable(self, exponent: Exponent) -> bool {
+        !self.is_root_finding() && exponent.re == 2.0 && exponent.im == 0.0
+    }
+
+    /// Whether the orbit stops at `z`, coming from `prev_z`
+    fn has_bailed_out(self, constants: &FragmentConstants, prev_z: Vec2, z: Vec2) -> bool {
+        if self.is_root_finding() {
+            z.distance_squared(prev_z) < CONVERGENCE_TOLERANCE * CONVERGENCE_TOLERANCE
+        } else {
+            z.length_squared() >= constants.escape_radius_sq()
+        }
+    }
+
+    /// How far the last step of an orbit that bailed out at `z` went past the bailout, between
+    /// 0 and 1, which smooths the iteration count
+    fn proximity(self, constants: &FragmentConstants, prev_z: Vec2, z: Vec2) -> f32 {
+        if self.is_root_finding() {
+            // The step is squared each iteration as the orbit converges
+            let ratio = z.distance(prev_z).ln() / CONVERGENCE_TOLERANCE.ln();
+            (1.0 - ratio.log2()).clamp(0.0, 1.0)
+        } else {
+            get_proximity(
+                prev_z.length(),
+                z.length(),
+                constants.escape_radius,
+                constants.exponent.re,
+            )
+        }
+    }
+}
+
+impl Step for Formula {
+    fn step(
+        self,
+        constants: &FragmentConstants,
+        newton: &Newton,
+        z: Complex,
+        c: Complex,
+        arg: &mut f32,
+    ) -> Complex {
+        if self == Formula::Newton {
+            let (s1, _) = newton.sums(z);
+            return z - Complex::new(newton.relaxation, 0.0) / s1;
+        }
+        let (power, _) = constants.exponent.pow(self.fold_z(z.into()).into(), arg);
+        Complex::from(self.fold_power(power.into())) + c
+    }
+
+    fn derivative(
+        self,
+        constants: &FragmentConstants,
+        newton: &Newton,
+        z: Complex,
+        der: Complex,
+        der_c: Complex,
+        mut arg: f32,
+    ) -> Complex {
+        if self == Formula::Newton {
+            // 1 - a (1 - p p'' / p'^2)
+            let (s1, s2) = newton.sums(z);
+            let der_step = Complex::new(1.0, 0.0) - newton.relaxation * s2 / (s1 * s1);
+            return der_step * der + der_c;
+        }
+        let exponent = constants.exponent;
+        let w = self.fold_z(z.into());
+        let der_w = fold_der(z.into(), w, der);
+        let (power, power_less_one) = exponent.pow(w.into(), &mut arg);
+        let der_power = exponent.complex() * power_less_one * der_w;
+        fold_der(power.into(), self.fold_power(power.into()), der_power) + der_c
+    }
+
+    fn second_derivative(self, constants: &FragmentConstants, z: Complex, mut arg: f32) -> Complex {
+        // e (e - 1) z^(e - 2), with z^(e - 2) taken as the lower power of the exponent less one
+        let exponent = constants.exponent;
+        let exponent_less_one = Exponent {
+            re: exponent.re - 1.0,
+            ..exponent
+        };
+        let (_, power_less_two) = exponent_less_one.pow(z, &mut arg);
+        exponent.complex() * exponent_less_one.complex() * power_less_two
+    }
+
+    fn step_quadratic<T: Quadratic>(self, z: T, c: T) -> T {
+        z.fold(|z| self.fold_z(z))
+            .square()
+            .fold(|w| self.fold_power(w))
+            + c
+    }
+
+    fn perturb(self, reference: Complex, dz: Complex, dc: Complex) -> Complex {
+        let Vec2 { x: a, y: b } = reference.into();
+        let Vec2 { x, y } = dz.into();
+        // Offsets of the real and imaginary part of z^2
+        let dre = (2.0 * a + x) * x - (2.0 * b + y) * y;
+        let dim = 2.0 * (a * y + x * b + x * y);
+        // The folds are perturbed with `diffabs` so that deep zooms keep their precision
+        let folded_dre = diffabs(a * a - b * b, dre);
+        // Offsets of -2x|y| and -2|x|y for the perpendicular variants
+        let perpendicular_dim = -2.0 * (a * diffabs(b, y) + x * (b + y).abs());
+        let perpendicular_buffalo_dim = -2.0 * (diffabs(a, x) * b + (a + x).abs() * y);
+        let d = match self {
+            Formula::Mandelbrot => vec2(dre, dim),
+            Formula::BurningShip => vec2(dre, 2.0 * diffabs(a * b, 0.5 * dim)),
+            Formula::PerpendicularBurningShip => vec2(dre, perpendicular_dim),
+            Formula::Celtic => vec2(folded_dre, dim),
+            Formula::PerpendicularCeltic => vec2(folded_dre, perpendicular_dim),
+            Formula::Buffalo => vec2(folded_dre, 2.0 * diffabs(a * b, 0.5 * dim)),
+            Formula::PerpendicularBuffalo => vec2(folded_dre, perpendicular_buffalo_dim),
+            // conj(Z + z)^2 - conj(Z)^2 is the conjugate of the offset of z^2
+            Formula::Tricorn => vec2(dre, -dim),
+            // Root finding is never perturbed
+            Formula::Newton => return dz,
+        };
+        Complex::from(d) + dc
+    }
+
+    fn is_analytic(self) -> bool {
+        self == Formula::Mandelbrot
+    }
+
+    fn is_root_finding(self) -> bool {
+        self == Formula::Newton
+    }
+}
+
+/// `der` carried through a fold from `z` to `folded`, which flips it along the same axes
+fn fold_der(z: Vec2, folded: Vec2, der: Complex) -> Complex {
+    (folded.signum() * z.signum() * Vec2::from(der)).into()
+}
+
+/// `|a + b| - |a|`, without the cancellation of working it out directly
+fn diffabs(a: f32, b: f32) -> f32 {
+    if a >= 0.0 {
+        if a + b >= 0.0 { b } else { -(2.0 * a + b) }
+    } else if a + b > 0.0 {
+        2.0 * a + b
+    } else {
+        -b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUADRATIC_FORMULAS: [Formula; 8] = [
+        Formula::Mandelbrot,
+        Formula::BurningShip,
+        Formula::PerpendicularBurningShip,
+        Formula::Celtic,
+        Formula::PerpendicularCeltic,
+        Formula::Buffalo,
+        Formula::PerpendicularBuffalo,
+        Formula::Tricorn,
+    ];
+
+    #[test]
+    fn diffabs_matches_the_difference_of_abs() {
+        // Every combination of the signs of `a` and `a + b`
+        for a in [-2.0f32, -0.5, 0.0, 0.5, 2.0] {
+            for b in [-3.0f32, -1.0, -0.25, 0.0, 0.25, 1.0, 3.0] {
+                assert_eq!(diffabs(a, b), (a + b).abs() - a.abs(), "a = {a}, b = {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn step_quadratic_matches_step() {
+        let constants = FragmentConstants::default();
+        let newton = Newton::default();
+        let c = Complex::new(-0.6, 0.4);
+        for formula in QUADRATIC_FORMULAS {
+            for z in [Complex::new(0.3, -0.7), Complex::new(-0.8, 0.2)] {
+                let expected = formula.step(&constants, &newton, z, c, &mut 0.0);
+                let quadratic = formula.step_quadratic(z, c);
+                assert!(
+                    (quadratic - expected).abs() < 1e-6,
+                    "{formula:?} at {z:?}: {quadratic:?} != {expected:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn perturb_matches_the_difference_of_steps() {
+        let constants = FragmentConstants::default();
+        let newton = Newton::default();
+        let c = Complex::new(-0.6, 0.4);
+        let dc = Complex::new(5e-4, -2.5e-4);
+        // Some references lie close enough to an axis for the offsets to cross the folds
+        let references = [
+            Complex::new(0.3, -0.7),
+            Complex::new(-0.8, 0.2),
+            Complex::new(5e-4, -0.6),
+            Complex::new(0.5, 4e-4),
+            Complex::new(-3e-4, 2e-4),
+        ];
+        let offsets = [
+            Complex::new(1e-3, 2e-3),
+            Complex::new(-2e-3, 1e-3),
+            Complex::new(-1e-3, -1e-3),
+            Complex::new(2e-3, -3e-3),
+        ];
+        for formula in QUADRATIC_FORMULAS {
+            for reference in references {
+                for dz in offsets {
+                    let step = |z, c| formula.step(&constants, &newton, z, c, &mut 0.0);
+                    let expected = step(reference + dz, c + dc) - step(reference, c);
+                    let perturbed = formula.perturb(reference, dz, dc);
+                    assert!(
+                        (perturbed - expected).abs() < 1e-5,
+                        "{formula:?} at {reference:?} + {dz:?}: {perturbed:?} != {expected:?}"
+                    );
+                }
+            }
+        }
+    }
+}
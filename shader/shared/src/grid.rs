@@ -14,6 +14,11 @@ impl<'a, T: Copy> GridRef<'a, T> {
     pub fn get(&self, p: UVec2) -> T {
         self.buffer[(p.y * self.size.x + p.x) as usize]
     }
+
+    /// Whether the buffer reaches as far as `p`, as it may hold fewer rows than the grid has
+    pub fn contains(&self, p: UVec2) -> bool {
+        ((p.y * self.size.x + p.x) as usize) < self.buffer.len()
+    }
 }
 
 pub struct GridRefMut<'a, T> {
@@ -1,6 +1,11 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
+pub mod colour;
+pub mod colour_space;
+pub mod formula;
 pub mod grid;
+pub mod orbit;
+pub mod palette;
 pub mod push_constants;
 
 pub use abels_complex as complex;
@@ -10,12 +15,29 @@ use glam::*;
 use spirv_std::num_traits::real::Real;
 
 pub const MARKER_RADIUS: f32 = 8.0;
+/// The grids are allocated for the window, up to as many pixels as this holds
 pub const GRID_SIZE: UVec2 = uvec2(2880, 1620);
+/// Larger windows iterate every pixel to completion in a single frame
+pub const MAX_TIME_SLICED_PIXELS: u32 = 1920 * 1080;
+/// Number of evenly ranked samples of the view's colour values used for histogram equalisation
+pub const HISTOGRAM_SIZE: u32 = 1024;
+/// Enough for the 256 colours of a Fractint map
+pub const MAX_GRADIENT_STOPS: u32 = 256;
+/// Colouring layers that can be stacked over the base colouring
+pub const MAX_LAYERS: usize = 3;
+/// Roots of the polynomial whose Newton fractal is drawn
+pub const MAX_ROOTS: usize = 8;
 
-// Given lerp(x, y, a) = e, x < e, y >= e
-// Returns 'a' which is a value between 0 and 1
-pub fn get_proximity(x: f32, y: f32, e: f32) -> f32 {
-    f32::inverse_lerp(x, y, e).powf(1.0 / (1.0 + e.log10()))
+// Given the norms x < e <= y of the last two iterations of an orbit escaping the radius e,
+// returns a value between 0 and 1 that approaches 1 as y approaches e
+pub fn get_proximity(x: f32, y: f32, e: f32, degree: f32) -> f32 {
+    if degree > 1.0 && degree != 2.0 {
+        // ln|z| grows by a factor of the degree each iteration, which holds for fractional
+        // degrees as well
+        (1.0 - (y.ln() / e.ln()).ln() / degree.ln()).clamp(0.0, 1.0)
+    } else {
+        f32::inverse_lerp(x, y, e).powf(1.0 / (1.0 + e.log10()))
+    }
 }
 
 pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
@@ -24,3 +46,39 @@ pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
     // Evaluate polynomial
     x * x * (3.0 - 2.0 * x)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The smoothed escape count of an orbit whose norm is raised to the `degree` each
+    /// iteration, and lands on `e.powf(t)` at the third
+    fn smooth_count(t: f32, e: f32, degree: f32) -> f32 {
+        let norm = |k: i32| e.powf(t * degree.powi(k - 3));
+        let mut k = 1;
+        while norm(k) < e {
+            k += 1;
+        }
+        k as f32 + get_proximity(norm(k - 1), norm(k), e, degree)
+    }
+
+    fn assert_continuous(e: f32, degree: f32) {
+        // Either side of the orbit that lands exactly on the escape radius, where the escape
+        // count steps from one iteration to the next
+        let below = smooth_count(1.0 - 1e-5, e, degree);
+        let above = smooth_count(1.0 + 1e-5, e, degree);
+        assert!(
+            (below - above).abs() < 0.02,
+            "{below} != {above} for the radius {e} and degree {degree}"
+        );
+    }
+
+    #[test]
+    fn proximity_is_continuous_across_an_iteration() {
+        for e in [2.0, 10.0, 1000.0] {
+            assert_continuous(e, 2.0);
+            assert_continuous(e, 2.5);
+            assert_continuous(e, 3.0);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::complex::Complex;
+use crate::formula::*;
+use crate::push_constants::shader::*;
+use glam::*;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::real::Real;
+
+/// Where a pixel lies, as far as colouring its orbit is concerned
+#[derive(Clone, Copy)]
+pub struct Pixel {
+    pub julia: bool,
+    pub c: Vec2,
+    /// Size of the pixel in the complex plane
+    pub size: f32,
+}
+
+impl Pixel {
+    /// The pixel whose center is at `coord`
+    pub fn new(constants: &FragmentConstants, coord: Vec2) -> Self {
+        let size = constants.size.as_vec2();
+        let n = if size.x > size.y { Vec2::X } else { Vec2::Y };
+        let julia = bool::from(constants.render_julia_set)
+            && coord.dot(n) > size.dot(n) * constants.render_split;
+        if julia {
+            Self {
+                julia,
+                c: constants.marker,
+                size: 1.0 / constants.julia_camera_zoom / size.y,
+            }
+        } else {
+            let zoom = constants.mandelbrot_camera_zoom;
+            Self {
+                julia,
+                c: (coord - 0.5 * size) / size.y / zoom + constants.mandelbrot_camera_translate,
+                size: 1.0 / zoom / size.y,
+            }
+        }
+    }
+}
+
+/// Whether the orbit counts as inside the set. Unfinished orbits are shown as inside until they
+/// bail out
+pub fn is_inside(constants: &FragmentConstants, end: OrbitEnd, i: u32) -> bool {
+    let num_iters = constants.num_iterations as u32 + 1;
+    let bailed_out = constants
+        .formula
+        .has_bailed_out(constants, end.zs[1], end.zs[2]);
+    !bailed_out || (i == num_iters && proximity(constants, end) > constants.num_iterations.fract())
+}
+
+/// How far the final iteration went past the bailout
+fn proximity(constants: &FragmentConstants, end: OrbitEnd) -> f32 {
+    constants.formula.proximity(constants, end.zs[1], end.zs[2])
+}
+
+/// What the `n`th iteration, from `prev_z` to `z`, adds to the sums of the styles that sum over
+/// the orbit
+#[derive(Clone, Copy)]
+struct SumTerms {
+    distance: f32,
+    norm: f32,
+    angle: f32,
+    stripe: f32,
+    triangle_inequality: f32,
+}
+
+impl SumTerms {
+    fn new(constants: &FragmentConstants, c_norm: f32, n: u32, prev_z: Vec2, z: Vec2) -> Self {
+        let arg = Complex::from(z).arg();
+        let stripe = if n > constants.stripe_skip {
+            0.5 * (constants.stripe_density * arg).sin() + 0.5
+        } else {
+            0.0
+        };
+        // |z^e| and |c| bound |z^e + c| from below and above
+        let prev_norm_pow = prev_z.length().powf(constants.exponent.re);
+        let lower = (prev_norm_pow - c_norm).abs();
+        let upper = prev_norm_pow + c_norm;
+        let triangle_inequality = if n > 1 && upper > lower {
+            (z.length() - lower) / (upper - lower)
+        } else {
+            0.0
+        };
+        Self {
+            distance: prev_z.distance(z),
+            norm: z.length(),
+            angle: arg.abs(),
+            stripe,
+            triangle_inequality,
+        }
+    }
+}
+
+impl OrbitSums {
+    /// Adds the `n`th iteration, from `prev_z` to `z`, of an orbit with `|c| = c_norm`
+    pub fn add(
+        &mut self,
+        constants: &FragmentConstants,
+        c_norm: f32,
+        n: u32,
+        prev_z: Vec2,
+        z: Vec2,
+    ) {
+        let terms = SumTerms::new(constants, c_norm, n, prev_z, z);
+        self.distance += terms.distance;
+        self.norm += terms.norm;
+        self.angle += terms.angle;
+        self.stripe += terms.stripe;
+        self.triangle_inequality += terms.triangle_inequality;
+        self.trap = self.trap.min(constants.orbit_trap.distance(prev_z));
+    }
+
+    /// The sums before the last of the `i` iterations of an orbit that ended at `zs`, which the
+    /// smoothing blends from. The terms of that iteration are worked out again rather than kept
+    fn before_last(
+        self,
+        constants: &FragmentConstants,
+        c_norm: f32,
+        i: u32,
+        zs: [Vec2; 3],
+    ) -> Self {
+        if i == 0 {
+            return self;
+        }
+        let terms = SumTerms::new(constants, c_norm, i, zs[1], zs[2]);
+        Self {
+            distance: self.distance - terms.distance,
+            norm: self.norm - terms.norm,
+            angle: self.angle - terms.angle,
+            stripe: self.stripe - terms.stripe,
+            triangle_inequality: self.triangle_inequality - terms.triangle_inequality,
+            ..self
+        }
+    }
+}
+
+/// The render parameters of an orbit coloured with `style`, worked out from what was kept of it
+/// so that changing the style doesn't need iterating again
+pub fn render_parameters(
+    constants: &FragmentConstants,
+    newton: &Newton,
+    style: RenderStyle,
+    pixel: Pixel,
+    end: OrbitEnd,
+    features: OrbitFeatures,
+    sums: OrbitSums,
+) -> RenderParameters {
+    let zs = end.zs;
+    let i = features.i;
+    let inside = is_inside(constants, end, i);
+    let h = proximity(constants, end);
+    let new = |x0: f32, x1: f32| RenderParameters::new(constants, inside, i, h, x0, x1);
+    let iterations = new(i as f32, (i + 1) as f32);
+    let prev_sums = || sums.before_last(constants, pixel.c.length(), i, zs);
+
+    let mut render_parameters = match style {
+        RenderStyle::Iterations => iterations,
+        RenderStyle::FinalAngle => new(
+            Complex::from(zs[1]).arg().abs(),
+            Complex::from(zs[2]).arg().abs(),
+        ),
+        RenderStyle::FinalDistance => new(zs[0].distance(zs[1]), zs[1].distance(zs[2])),
+        RenderStyle::FinalNorm => new(zs[1].length(), zs[2].length()),
+        RenderStyle::DistanceEstimate => {
+            let log_distance = if inside {
+                // The interior estimate measures distances in the parameter plane only
+                if !pixel.julia && features.cycle_period > 0 {
+                    (features.cycle_distance / pixel.size).ln()
+                } else {
+                    0.0
+                }
+            } else {
+                let norm = zs[2].length();
+                let distance = norm * norm.ln() / Vec2::from_array(features.der).length();
+                (distance / pixel.size).ln()
+            };
+            new(log_distance, log_distance)
+        }
+        RenderStyle::BinaryDecomposition => {
+            let mut render_parameters = iterations;
+            if !inside {
+                render_parameters.y = Complex::from(zs[2]).arg();
+            }
+            render_parameters
+        }
+        RenderStyle::Period | RenderStyle::MultiplierNorm | RenderStyle::MultiplierAngle => {
+            let num_iters = constants.num_iterations as u32 + 1;
+            if !inside || i < num_iters {
+                iterations
+            } else {
+                let multiplier = if features.cycle_period > 0 {
+                    Vec2::from_array(features.der)
+                } else {
+                    Vec2::ZERO
+                };
+                let x = match style {
+                    RenderStyle::Period => features.cycle_period as f32,
+                    RenderStyle::MultiplierNorm => multiplier.length(),
+                    _ => Complex::from(multiplier).arg() / core::f32::consts::TAU + 0.5,
+                };
+                new(x, x)
+            }
+        }
+        RenderStyle::Root => {
+            // The root picks the colour, and the smoothed iteration count shades it
+            let root = newton.nearest_root(zs[2]) as f32;
+            let mut render_parameters = new(root, root);
+            render_parameters.y = iterations.x;
+            render_parameters
+        }
+        RenderStyle::DistanceSum => new(prev_sums().distance, sums.distance),
+        RenderStyle::NormSum => new(prev_sums().norm, sums.norm),
+        RenderStyle::AngleSum => new(prev_sums().angle, sums.angle),
+        RenderStyle::StripeAverage => {
+            let skip = constants.stripe_skip;
+            let count = if i > skip { (i - skip) as f32 } else { 0.0 };
+            new(
+                prev_sums().stripe / (count - 1.0).max(1.0),
+                sums.stripe / count.max(1.0),
+            )
+        }
+        RenderStyle::TriangleInequalityAverage => {
+            let count = if i > 1 { (i - 1) as f32 } else { 0.0 };
+            new(
+                prev_sums().triangle_inequality / (count - 1.0).max(1.0),
+                sums.triangle_inequality / count.max(1.0),
+            )
+        }
+        RenderStyle::OrbitTrap => {
+            let trap = constants.orbit_trap;
+            let min_distance = sums.trap.min(trap.distance(zs[2]));
+            let prev_min_distance = if i > 0 { sums.trap } else { min_distance };
+            new(prev_min_distance / trap.size, min_distance / trap.size)
+        }
+        RenderStyle::ImageTrap => {
+            let mut render_parameters = iterations;
+            render_parameters.trap_colour = sums.image_trap;
+            render_parameters
+        }
+        RenderStyle::AtomDomain => {
+            let atom_period = features.atom_period as f32;
+            let mut render_parameters = new(atom_period, atom_period);
+            render_parameters.y = features.atom_ratio;
+            render_parameters
+        }
+    };
+    if constants.slope_shading.into() && !inside {
+        // The gradient of the potential points along z / (dz/dc)
+        let z = zs[2];
+        let der = Vec2::from_array(features.der);
+        let normal = vec2(z.x * der.x + z.y * der.y, z.y * der.x - z.x * der.y);
+        render_parameters.normal_angle = normal.y.atan2(normal.x);
+    }
+    render_parameters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn before_last_undoes_the_last_iteration() {
+        let constants = FragmentConstants {
+            stripe_density: 3.0,
+            stripe_skip: 1,
+            ..Default::default()
+        };
+        let newton = Newton::default();
+        let c = Complex::new(-0.6, 0.4);
+        let c_norm = c.abs();
+        let mut zs = [Vec2::ZERO; 3];
+        let mut sums = OrbitSums::new();
+        let mut prev_sums = sums;
+        for n in 1..=8 {
+            let z = constants
+                .formula
+                .step(&constants, &newton, zs[2].into(), c, &mut 0.0);
+            prev_sums = sums;
+            sums.add(&constants, c_norm, n, zs[2], z.into());
+            zs = [zs[1], zs[2], z.into()];
+        }
+        let before = sums.before_last(&constants, c_norm, 8, zs);
+        let pairs = [
+            (before.distance, prev_sums.distance),
+            (before.norm, prev_sums.norm),
+            (before.angle, prev_sums.angle),
+            (before.stripe, prev_sums.stripe),
+            (before.triangle_inequality, prev_sums.triangle_inequality),
+        ];
+        for (k, (before, expected)) in pairs.into_iter().enumerate() {
+            assert!(
+                (before - expected).abs() < 1e-5,
+                "sum {k}: {before} != {expected}"
+            );
+        }
+        // The trap leaves out the final `z`, so it already is the minimum before it
+        assert_eq!(before.trap, sums.trap);
+    }
+}
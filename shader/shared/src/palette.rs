@@ -0,0 +1,137 @@
+use crate::colour_space;
+use crate::push_constants::shader::{CosinePalette, GradientStop, Interpolation};
+use core::f32::consts::TAU;
+use glam::*;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::real::Real;
+
+pub fn pal(t: f32, a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> Vec3 {
+    a + b * cos_vec3(TAU * (c * t + d))
+}
+
+fn cos_vec3(v: Vec3) -> Vec3 {
+    vec3(v.x.cos(), v.y.cos(), v.z.cos())
+}
+
+/// Interpolation between the first `num_stops` stops given by `stop`, which are sorted by
+/// position. Repeats with a period of 1, blending from the last stop back into the first.
+/// Two stops at the same position make a hard edge. The stops are looked up by index so that the
+/// gpu can read them from an array in `Colourings` and the cpu from a `Vec`
+pub fn gradient(
+    stop: impl Fn(usize) -> GradientStop,
+    num_stops: u32,
+    interpolation: Interpolation,
+    t: f32,
+) -> Vec3 {
+    let n = num_stops as usize;
+    if n == 0 {
+        return Vec3::ZERO;
+    }
+    let t = t - t.floor();
+    // Binary search for the first stop after `t`
+    let mut lo = 0;
+    let mut hi = n;
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if stop(mid).position <= t {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    let next = lo;
+    // Before the first or after the last stop, blend between the last and first
+    let (a, b) = if next == 0 || next == n {
+        (stop(n - 1), stop(0))
+    } else {
+        (stop(next - 1), stop(next))
+    };
+    let mut start = a.position;
+    let mut end = b.position;
+    if next == 0 {
+        start -= 1.0;
+    } else if next == n {
+        end += 1.0;
+    }
+    let s = if end > start {
+        (t - start) / (end - start)
+    } else {
+        0.0
+    };
+    colour_space::mix(
+        Vec3::from_array(a.colour),
+        Vec3::from_array(b.colour),
+        s,
+        interpolation,
+    )
+}
+
+pub const ZEBRA: CosinePalette = CosinePalette {
+    a: [0.5, 0.5, 0.5],
+    b: [0.5, 0.5, 0.5],
+    c: [1.0, 1.0, 1.0],
+    d: [0.0, 0.0, 0.0],
+};
+
+pub const RGB: CosinePalette = CosinePalette {
+    a: [0.5, 0.5, 0.5],
+    b: [0.5, 0.5, 0.5],
+    c: [1.0, 1.0, 1.0],
+    d: [0.0, 0.33, 0.67],
+};
+
+pub const NEON_A: CosinePalette = CosinePalette {
+    a: [0.5, 0.5, 0.5],
+    b: [0.5, 0.5, 0.5],
+    c: [1.0, 1.0, 0.5],
+    d: [0.8, 0.90, 0.30],
+};
+
+pub const NEON_B: CosinePalette = CosinePalette {
+    a: [0.6, 0.6, 0.6],
+    b: [0.3, 0.3, 0.3],
+    c: [1.0, 2.0, 0.5],
+    d: [0.5, 0.0, 0.67],
+};
+
+pub const NEON_C: CosinePalette = CosinePalette {
+    a: [0.6, 0.6, 0.6],
+    b: [0.3, 0.3, 0.3],
+    c: [0.5, 2.0, 1.0],
+    d: [0.0, 0.5, 0.67],
+};
+
+pub const PASTEL: CosinePalette = CosinePalette {
+    a: [0.9, 0.8, 0.8],
+    b: [0.5, 0.2, 0.3],
+    c: [1.0, 2.0, 1.0],
+    d: [0.0, 0.5, 0.67],
+};
+
+pub const COPPER: CosinePalette = CosinePalette {
+    a: [0.66, 0.6, 0.6],
+    b: [0.3, 0.3, 0.3],
+    c: [1.0, 1.0, 1.0],
+    d: [0.33, 0.20, 0.20],
+};
+
+pub const RED_AND_BLACK: CosinePalette = CosinePalette {
+    a: [0.5, 0.0, 0.0],
+    b: [0.5, 0.0, 0.0],
+    c: [1.0, 0.0, 0.0],
+    d: [0.0, 0.0, 0.0],
+};
+
+pub const SOLARIZED_DARK: CosinePalette = CosinePalette {
+    a: [0.5, 0.5, 0.5],
+    b: [0.5, 0.5, 0.5],
+    c: [2.0, 1.0, 0.0],
+    d: [0.5, 0.20, 0.25],
+};
+
+pub const HIGHLIGHTER: CosinePalette = CosinePalette {
+    a: [0.8, 0.5, 0.4],
+    b: [0.2, 0.4, 0.2],
+    c: [2.0, 1.0, 1.0],
+    d: [0.0, 0.333, 0.667],
+};
@@ -1,5 +1,5 @@
 #[cfg(not(target_arch = "spirv"))]
-use bytemuck::NoUninit;
+use bytemuck::{AnyBitPattern, NoUninit};
 use glam::*;
 
 pub mod shader;
@@ -36,7 +36,7 @@ impl From<UVec2> for Size {
 }
 
 #[derive(Copy, Clone, Debug, Default)]
-#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, AnyBitPattern))]
 #[repr(C)]
 pub struct Bool(pub u32);
 
@@ -1,10 +1,15 @@
 use super::*;
+use crate::formula::*;
 use crate::*;
 #[cfg(not(target_arch = "spirv"))]
-use bytemuck::NoUninit;
+use bytemuck::{AnyBitPattern, NoUninit};
+#[cfg(not(target_arch = "spirv"))]
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::real::Real;
 
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
-#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
 #[repr(u32)]
 pub enum RenderStyle {
     #[default]
@@ -15,10 +20,149 @@ pub enum RenderStyle {
     NormSum,
     FinalNorm,
     AngleSum,
+    DistanceEstimate,
+    StripeAverage,
+    TriangleInequalityAverage,
+    OrbitTrap,
+    ImageTrap,
+    Period,
+    MultiplierNorm,
+    MultiplierAngle,
+    BinaryDecomposition,
+    AtomDomain,
+    /// The root a Newton orbit converged to, shaded by how long it took
+    Root,
 }
 
+/// Reshapes a style's values before they are scaled onto the palette
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
-#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum Transfer {
+    #[default]
+    Linear,
+    Log,
+    Sqrt,
+    Cbrt,
+    /// Raises the values to the colouring's `exponent`
+    Power,
+    /// The fraction of the view's values that are smaller, which spreads them evenly over the
+    /// palette. Only the outside is ranked
+    Rank,
+}
+
+/// What happens past the ends of the palette
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum Wrap {
+    #[default]
+    Repeat,
+    /// Runs back and forth along the palette
+    Mirror,
+    Clamp,
+}
+
+impl Wrap {
+    /// Brings `t` into the first period of a palette that repeats every `period`
+    pub fn apply(self, t: f32, period: f32) -> f32 {
+        match self {
+            Wrap::Repeat => t,
+            Wrap::Mirror => {
+                let u = t / (2.0 * period);
+                let u = 2.0 * period * (u - u.floor());
+                u.min(2.0 * period - u)
+            }
+            // Stops just short of the end, which would wrap around to the start
+            Wrap::Clamp => t.clamp(0.0, period * (1.0 - 1e-5)),
+        }
+    }
+}
+
+/// How the pixels on one side of the boundary, inside or outside, are coloured
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[cfg_attr(not(target_arch = "spirv"), serde(default))]
+#[repr(C)]
+pub struct Colouring {
+    pub palette: Palette,
+    pub style: RenderStyle,
+    pub period: f32,
+    /// Added to the position on the palette
+    pub offset: f32,
+    pub transfer: Transfer,
+    /// Exponent of the `Power` transfer function
+    pub exponent: f32,
+    pub wrap: Wrap,
+}
+
+impl Default for Colouring {
+    fn default() -> Self {
+        Self {
+            palette: Palette::default(),
+            style: RenderStyle::default(),
+            period: 0.5,
+            offset: 0.0,
+            transfer: Transfer::default(),
+            exponent: 0.5,
+            wrap: Wrap::default(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+}
+
+impl BlendMode {
+    /// Blends `layer` over `base`, both in linear light
+    pub fn blend(self, base: Vec3, layer: Vec3) -> Vec3 {
+        match self {
+            BlendMode::Normal => layer,
+            BlendMode::Multiply => base * layer,
+            BlendMode::Screen => 1.0 - (1.0 - base) * (1.0 - layer),
+            BlendMode::Overlay => Vec3::select(
+                base.cmplt(Vec3::splat(0.5)),
+                2.0 * base * layer,
+                1.0 - 2.0 * (1.0 - base) * (1.0 - layer),
+            ),
+            BlendMode::Add => base + layer,
+        }
+    }
+}
+
+/// A colouring drawn over the base colouring of `region`
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(C)]
+pub struct Layer {
+    pub colouring: Colouring,
+    pub blend_mode: BlendMode,
+    pub opacity: f32,
+    pub region: RenderPartitioning,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            colouring: Colouring::default(),
+            blend_mode: BlendMode::default(),
+            opacity: 0.5,
+            region: RenderPartitioning::Outside,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
 #[repr(u32)]
 pub enum Palette {
     #[default]
@@ -32,14 +176,120 @@ pub enum Palette {
     NeonA,
     NeonB,
     NeonC,
+    Custom,
+    Cosine,
 }
 
-#[derive(Copy, Clone, Debug, Default)]
-#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+impl Palette {
+    /// The smallest positive `t` for which `get_col(self, x + t) == get_col(self, x)`
+    pub fn period(self) -> f32 {
+        match self {
+            Palette::NeonA | Palette::NeonB | Palette::NeonC => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    /// The parameters of a built-in cosine palette, or `custom` for the editable ones
+    pub fn cosine(self, custom: CosinePalette) -> CosinePalette {
+        match self {
+            Palette::RGB => palette::RGB,
+            Palette::Zebra => palette::ZEBRA,
+            Palette::Copper => palette::COPPER,
+            Palette::NeonA => palette::NEON_A,
+            Palette::SolarizedDark => palette::SOLARIZED_DARK,
+            Palette::Highlighter => palette::HIGHLIGHTER,
+            Palette::Pastel => palette::PASTEL,
+            Palette::RedAndBlack => palette::RED_AND_BLACK,
+            Palette::NeonB => palette::NEON_B,
+            Palette::NeonC => palette::NEON_C,
+            Palette::Custom | Palette::Cosine => custom,
+        }
+    }
+}
+
+/// `a + b * cos(tau * (c * t + d))` in sRGB. The vectors are arrays rather than `Vec3`s, whose
+/// layout differs between the cpu and gpu
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
 #[repr(C)]
+pub struct CosinePalette {
+    /// Offset
+    pub a: [f32; 3],
+    /// Amplitude
+    pub b: [f32; 3],
+    /// Frequency
+    pub c: [f32; 3],
+    /// Phase
+    pub d: [f32; 3],
+}
+
+impl Default for CosinePalette {
+    fn default() -> Self {
+        palette::RGB
+    }
+}
+
+impl CosinePalette {
+    pub fn col(&self, t: f32) -> Vec3 {
+        palette::pal(
+            t,
+            Vec3::from_array(self.a),
+            Vec3::from_array(self.b),
+            Vec3::from_array(self.c),
+            Vec3::from_array(self.d),
+        )
+    }
+}
+
+/// How colours are blended between the stops of a gradient
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum Interpolation {
+    Srgb,
+    #[default]
+    Oklab,
+    /// Like `Oklab`, but going around the hue circle rather than through grey
+    Oklch,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum Dither {
+    None,
+    #[default]
+    Ordered,
+    /// Interleaved gradient noise, which looks less patterned than `Ordered`
+    Noise,
+}
+
+/// An sRGB colour of a custom gradient, placed at `position` between 0 and 1.
+/// The colour is an array because the gpu aligns a `Vec3` to 16 bytes, which would give the
+/// storage buffer a different stride than the host
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(C)]
+pub struct GradientStop {
+    pub colour: [f32; 3],
+    pub position: f32,
+}
+
+const _: () = assert!(core::mem::size_of::<GradientStop>() == 16);
+
+#[derive(Copy, Clone, Debug, Default)]
 pub struct RenderParameters {
     pub i: u32,
     pub x: f32,
+    /// Angle of the surface normal used for slope shading
+    pub normal_angle: f32,
+    /// Whether the orbit stayed bounded, so that the inside colouring applies
+    pub inside: Bool,
+    /// Premultiplied colour picked up by an image trap, packed as rgba8
+    pub trap_colour: u32,
+    /// Style specific value besides `x`: the argument of the final `z` of escaped orbits for
+    /// binary decomposition, or the smallest `|z|` relative to `|z_1|` for atom domains
+    pub y: f32,
 }
 
 impl RenderParameters {
@@ -51,13 +301,6 @@ impl RenderParameters {
         x0: f32,
         x1: f32,
     ) -> Self {
-        let i = if (inside && constants.render_partitioning == RenderPartitioning::Outside)
-            || (!inside && constants.render_partitioning == RenderPartitioning::Inside)
-        {
-            core::u32::MAX
-        } else {
-            i
-        };
         let s = if inside {
             constants.num_iterations.fract() * constants.smooth_factor
         } else {
@@ -65,7 +308,108 @@ impl RenderParameters {
         };
         let x = x0.lerp(x1, s);
 
-        Self { i, x }
+        Self {
+            i,
+            x,
+            normal_angle: 0.0,
+            inside: inside.into(),
+            trap_colour: 0,
+            y: 0.0,
+        }
+    }
+}
+
+/// Progress of a single pixel's orbit besides what the grids of `OrbitEnd`, `OrbitFeatures` and
+/// `OrbitSums` hold of it, kept between frames so that the iterations can be spread over several
+/// frames
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct IterationState {
+    /// Offset of `z` from the reference orbit in perturbation mode
+    pub dz: Vec2,
+    pub ref_i: u32,
+    /// Argument of the last value raised to a non-integer exponent, on the branch picked for it
+    pub arg: f32,
+    pub finished: Bool,
+    /// `|z_1|`, which the atom domains are measured against
+    pub first_norm: f32,
+}
+
+// The buffers of these are indexed by the shader with the strides the host gives them, which
+// only agree while every `Vec2` sits at a multiple of 8 bytes. Each also stays under the 28
+// bytes a pixel of `GRID_SIZE` can take within the 128 MiB browsers allow a storage buffer
+const _: () = assert!(core::mem::size_of::<IterationState>() == 24);
+const _: () = assert!(core::mem::size_of::<OrbitEnd>() == 24);
+const _: () = assert!(core::mem::size_of::<OrbitFeatures>() == 28);
+const _: () = assert!(core::mem::size_of::<OrbitSums>() == 28);
+
+/// The last three values of `z` of a pixel's orbit, the final one being `zs[2]`. Kept with its
+/// `OrbitFeatures` so that the pixel can be recoloured without iterating again, unless the style
+/// that sums over the orbit changes
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, AnyBitPattern))]
+#[repr(C)]
+pub struct OrbitEnd {
+    pub zs: [Vec2; 3],
+}
+
+/// What else is kept of a pixel's orbit besides its `OrbitEnd`. The vectors are arrays to keep
+/// the size at 28 bytes on the gpu, where a `Vec2` would pad it to 32
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, AnyBitPattern))]
+#[repr(C)]
+pub struct OrbitFeatures {
+    /// Derivative of the final `z` with respect to `c`, or to `z0` for julia sets. For orbits
+    /// that end on an attracting cycle, the multiplier of that cycle instead
+    pub der: [f32; 2],
+    pub i: u32,
+    /// Zero unless the orbit ends on an attracting cycle
+    pub cycle_period: u32,
+    /// Distance to the boundary of the cycle's hyperbolic component in the parameter plane
+    pub cycle_distance: f32,
+    /// The iteration with the smallest `|z|`, which the atom domains are coloured by
+    pub atom_period: u32,
+    /// That smallest `|z|` relative to `|z_1|`, which is how much closer than the first
+    /// iteration the orbit came to zero
+    pub atom_ratio: f32,
+}
+
+impl OrbitFeatures {
+    pub fn new(der0: Vec2) -> Self {
+        Self {
+            der: der0.to_array(),
+            atom_ratio: 1.0,
+            ..Default::default()
+        }
+    }
+}
+
+/// The statistics of the styles that sum over the orbit, all of which are gathered while
+/// iterating so that switching between them doesn't need iterating again
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, AnyBitPattern))]
+#[repr(C)]
+pub struct OrbitSums {
+    pub distance: f32,
+    pub norm: f32,
+    pub angle: f32,
+    /// Counts the iterations after `FragmentConstants::stripe_skip` only
+    pub stripe: f32,
+    /// Counts the iterations after the first only
+    pub triangle_inequality: f32,
+    /// Smallest distance to the orbit trap, leaving out the final `z`
+    pub trap: f32,
+    /// Colour picked up by the image trap, premultiplied and packed as rgba8
+    pub image_trap: u32,
+}
+
+impl OrbitSums {
+    pub fn new() -> Self {
+        Self {
+            trap: f32::MAX,
+            ..Default::default()
+        }
     }
 }
 
@@ -79,7 +423,7 @@ pub enum IterationMode {
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
-#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
 #[repr(u32)]
 pub enum RenderPartitioning {
     #[default]
@@ -88,7 +432,100 @@ pub enum RenderPartitioning {
     Both,
 }
 
-#[derive(Copy, Clone, Debug)]
+impl RenderPartitioning {
+    pub fn shows(self, inside: bool) -> bool {
+        match self {
+            RenderPartitioning::Outside => !inside,
+            RenderPartitioning::Inside => inside,
+            RenderPartitioning::Both => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(C)]
+pub struct Light {
+    pub angle: f32,
+    pub elevation: f32,
+    pub intensity: f32,
+}
+
+impl Light {
+    /// Lambertian lighting of a surface whose slope points along `normal`
+    pub fn shade(&self, normal: Vec2) -> f32 {
+        let direction = Vec2::from_angle(self.angle);
+        let t = (normal.dot(direction) + self.elevation) / (1.0 + self.elevation);
+        1.0 - self.intensity + self.intensity * t.max(0.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum TrapShape {
+    #[default]
+    Point,
+    Line,
+    Cross,
+    Circle,
+    Ring,
+}
+
+/// The position is an array because the gpu aligns a `Vec2` to 8 bytes, which would shift the
+/// fields of `FragmentConstants` after it
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct OrbitTrap {
+    pub position: [f32; 2],
+    pub shape: TrapShape,
+    pub size: f32,
+    pub angle: f32,
+}
+
+impl OrbitTrap {
+    pub fn distance(&self, z: Vec2) -> f32 {
+        let p = z - Vec2::from_array(self.position);
+        let direction = Vec2::from_angle(self.angle);
+        match self.shape {
+            TrapShape::Point => p.length(),
+            TrapShape::Line => p.perp_dot(direction).abs(),
+            TrapShape::Cross => p.perp_dot(direction).abs().min(p.dot(direction).abs()),
+            TrapShape::Circle => (p.length() - self.size).max(0.0),
+            TrapShape::Ring => (p.length() - self.size).abs(),
+        }
+    }
+
+    /// Coordinates of `z` in a rectangle of height `size` and width `aspect * size`
+    /// centered on the trap, with (0, 0) and (1, 1) at opposite corners
+    pub fn image_uv(&self, z: Vec2, aspect: f32) -> Vec2 {
+        let p = Vec2::from_angle(-self.angle).rotate(z - Vec2::from_array(self.position));
+        p / (self.size * vec2(aspect, 1.0)) + 0.5
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit, Serialize, Deserialize))]
+#[repr(u32)]
+pub enum ImageTrapMode {
+    #[default]
+    FirstHit,
+    Blend,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct ImageTrap {
+    /// Width over height of the image
+    pub aspect: f32,
+    pub mode: ImageTrapMode,
+    /// Opacity of each hit when blending
+    pub opacity: f32,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
 #[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
 #[repr(C)]
 pub struct FragmentConstants {
@@ -105,22 +542,91 @@ pub struct FragmentConstants {
     pub num_points: u32,
     pub render_julia_set: Bool,
     pub render_split: f32,
-    pub palette: Palette,
     pub smooth_factor: f32,
     pub animate_time: f32,
-    pub palette_period: f32,
-    pub render_style: RenderStyle,
     pub mandelbrot_num_ref_iterations: u32,
     pub needs_reiterate_mandelbrot: Bool,
     pub needs_reiterate_julia: Bool,
+    pub resume_mandelbrot: Bool,
+    pub resume_julia: Bool,
+    pub time_sliced: Bool,
+    pub iterations_per_frame: u32,
     pub iteration_mode: IterationMode,
     pub render_partitioning: RenderPartitioning,
-    pub exponent: f32,
+    pub exponent: Exponent,
     pub escape_radius: f32,
+    pub distance_estimate_thickness: f32,
+    pub slope_shading: Bool,
+    pub light: Light,
+    pub stripe_density: f32,
+    pub stripe_skip: u32,
+    pub orbit_trap: OrbitTrap,
+    pub orbit_trap_screen_space: [f32; 2],
+    pub image_trap: ImageTrap,
+    /// Number of times the palette repeats over the histogram
+    pub histogram_cycles: f32,
+    /// Smooths binary decomposition into lines along the angle of the final `z` and along
+    /// equipotentials, which approximate the field lines
+    pub field_lines: Bool,
+    pub field_line_width: f32,
+    pub dither: Dither,
+    pub formula: Formula,
 }
 
+// Many devices only take 256 bytes of push constants, so anything larger goes in a storage
+// buffer, like `Colourings`
+const _: () = assert!(core::mem::size_of::<FragmentConstants>() <= 256);
+
 impl FragmentConstants {
     pub fn escape_radius_sq(&self) -> f32 {
         self.escape_radius * self.escape_radius
     }
 }
+
+/// The colourings of the inside, the outside and the layers over them, with the tables they look
+/// colours up in. Read from a single storage buffer, as they don't fit in the push constants and
+/// browsers only allow 8 storage buffers per shader stage
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(not(target_arch = "spirv"), derive(NoUninit))]
+#[repr(C)]
+pub struct Colourings {
+    pub outside: Colouring,
+    pub inside: Colouring,
+    pub layers: [Layer; MAX_LAYERS],
+    pub num_layers: u32,
+    pub cosine_palette: CosinePalette,
+    /// The roots picked from by the root colouring
+    pub newton: Newton,
+    /// The first `num_gradient_stops` are the custom palette, sorted by position
+    pub gradient: [GradientStop; MAX_GRADIENT_STOPS as usize],
+    pub num_gradient_stops: u32,
+    pub gradient_interpolation: Interpolation,
+    /// Quantiles of the view's colour values, used for histogram equalisation
+    pub histogram: [f32; HISTOGRAM_SIZE as usize],
+}
+
+impl Colourings {
+    pub fn colouring(&self, inside: bool) -> Colouring {
+        if inside { self.inside } else { self.outside }
+    }
+
+    /// Whether any of the pixels shown by `partitioning` are coloured with `style`
+    pub fn uses_style(&self, partitioning: RenderPartitioning, style: RenderStyle) -> bool {
+        let shows = |region: RenderPartitioning| {
+            (partitioning.shows(false) && region.shows(false))
+                || (partitioning.shows(true) && region.shows(true))
+        };
+        if (shows(RenderPartitioning::Outside) && self.outside.style == style)
+            || (shows(RenderPartitioning::Inside) && self.inside.style == style)
+        {
+            return true;
+        }
+        for k in 0..self.num_layers as usize {
+            let layer = self.layers[k];
+            if shows(layer.region) && layer.colouring.style == style {
+                return true;
+            }
+        }
+        false
+    }
+}